@@ -3,6 +3,7 @@ export interface Key {
     free(): void;
     readonly incoming_view_key: string;
     readonly outgoing_view_key: string;
+    readonly full_view_key: string;
     readonly public_address: string;
     readonly spending_key: string;
 }
@@ -27,6 +28,7 @@ export declare class Note {
     get value(): bigint;
     get memo(): string;
     nullifier(ownerPrivateKey: string, position: bigint): Buffer;
+    nullifierFromViewKey(ownerFullViewKey: string, position: bigint): Buffer;
 }
 export declare class NoteEncrypted {
     boxedData: unknown;
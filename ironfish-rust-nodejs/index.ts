@@ -7,6 +7,8 @@ export interface Key {
   
   readonly outgoing_view_key: string;
   
+  readonly full_view_key: string;
+  
   readonly public_address: string;
   
   readonly spending_key: string;
@@ -65,6 +67,10 @@ export class Note {
   nullifier(ownerPrivateKey: string, position: bigint): Buffer {
     return native.noteNullifier.call(this.boxedData, ownerPrivateKey, position.toString());
   }
+
+  nullifierFromViewKey(ownerFullViewKey: string, position: bigint): Buffer {
+    return native.noteNullifierFromViewKey.call(this.boxedData, ownerFullViewKey, position.toString());
+  }
 }
 
 export class NoteEncrypted {
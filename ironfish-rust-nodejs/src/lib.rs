@@ -12,6 +12,7 @@ struct Key {
     spending_key: String,
     incoming_view_key: String,
     outgoing_view_key: String,
+    full_view_key: String,
     public_address: String,
 }
 
@@ -28,6 +29,9 @@ impl Key {
         let outgoing_view_key = cx.string(&self.outgoing_view_key);
         obj.set(cx, "outgoing_view_key", outgoing_view_key)?;
 
+        let full_view_key = cx.string(&self.full_view_key);
+        obj.set(cx, "full_view_key", full_view_key)?;
+
         let public_address = cx.string(&self.public_address);
         obj.set(cx, "public_address", public_address)?;
 
@@ -43,6 +47,7 @@ fn generate_key(mut cx: FunctionContext) -> JsResult<JsObject> {
         spending_key: sapling_key.hex_spending_key(),
         incoming_view_key: sapling_key.incoming_view_key().hex_key(),
         outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
+        full_view_key: sapling_key.full_viewing_key().hex_key(),
         public_address: sapling_key.generate_public_address().hex_public_address(),
     };
 
@@ -59,6 +64,7 @@ fn generate_new_public_address(mut cx: FunctionContext) -> JsResult<JsObject> {
         spending_key: sapling_key.hex_spending_key(),
         incoming_view_key: sapling_key.incoming_view_key().hex_key(),
         outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
+        full_view_key: sapling_key.full_viewing_key().hex_key(),
         public_address: sapling_key.generate_public_address().hex_public_address(),
     };
 
@@ -99,6 +105,10 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("noteValue", structs::NativeNote::value)?;
     cx.export_function("noteMemo", structs::NativeNote::memo)?;
     cx.export_function("noteNullifier", structs::NativeNote::nullifier)?;
+    cx.export_function(
+        "noteNullifierFromViewKey",
+        structs::NativeNote::nullifier_from_view_key,
+    )?;
 
     cx.export_function(
         "simpleTransactionNew",
@@ -2,12 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 use neon::prelude::*;
 
 use ironfish_rust::note::Memo;
-use ironfish_rust::sapling_bls12::{Key, Note, SAPLING};
+use ironfish_rust::sapling_bls12::{FullViewingKey, Key, Note, SAPLING};
 
 pub struct NativeNote {
     pub(crate) note: Note,
@@ -28,8 +28,9 @@ impl NativeNote {
 
         let owner_address = ironfish_rust::PublicAddress::from_hex(SAPLING.clone(), &owner)
             .or_else(|err| cx.throw_error(err.to_string()))?;
+        let memo = Memo::try_from(memo).or_else(|err| cx.throw_error(err.to_string()))?;
         Ok(cx.boxed(NativeNote {
-            note: Note::new(SAPLING.clone(), owner_address, value_u64, Memo::from(memo)),
+            note: Note::new(SAPLING.clone(), owner_address, value_u64, memo),
         }))
     }
 
@@ -117,4 +118,34 @@ impl NativeNote {
 
         Ok(bytes)
     }
+
+    /// Compute the nullifier for this note, given the full viewing key of its
+    /// owner. Watch-only wallets use this to see when their notes are spent
+    /// without having the spending key.
+    pub fn nullifier_from_view_key(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+        let note = cx
+            .this()
+            .downcast_or_throw::<JsBox<NativeNote>, _>(&mut cx)?;
+        let owner_view_key = cx.argument::<JsString>(0)?.value(&mut cx);
+        // TODO: Should be BigInt, but no first-class Neon support
+        let position = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        let position_u64 = position
+            .parse::<u64>()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        let view_key = FullViewingKey::from_hex(SAPLING.clone(), &owner_view_key)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        let nullifier = note.note.nullifier_from_view_key(&view_key, position_u64);
+
+        let mut bytes = cx.buffer(nullifier.len().try_into().unwrap())?;
+
+        cx.borrow_mut(&mut bytes, |data| {
+            let slice = data.as_mut_slice();
+            slice.clone_from_slice(&nullifier[..slice.len()]);
+        });
+
+        Ok(bytes)
+    }
 }
@@ -24,7 +24,7 @@ impl NativeNoteEncrypted {
         let note = cx
             .borrow(&bytes, |data| {
                 let cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(data.as_slice());
-                MerkleNote::read(cursor, hasher)
+                MerkleNote::read(cursor, &hasher.jubjub)
             })
             .or_else(|err| cx.throw_error(err.to_string()))?;
 
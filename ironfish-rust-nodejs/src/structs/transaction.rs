@@ -28,7 +28,7 @@ impl NativeTransactionPosted {
         let transaction = cx
             .borrow(&bytes, |data| {
                 let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(data.as_slice());
-                Transaction::read(SAPLING.clone(), &mut cursor)
+                Transaction::read(SAPLING.verifier().clone(), &mut cursor)
             })
             .or_else(|err| cx.throw_error(err.to_string()))?;
 
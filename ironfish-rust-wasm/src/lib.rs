@@ -8,8 +8,10 @@ extern crate wasm_bindgen;
 use ironfish_rust::sapling_bls12;
 
 pub mod panic_hook;
+mod sapling;
 pub mod wasm_structs;
 
+pub use sapling::load_sapling_params;
 use std::str;
 use wasm_bindgen::prelude::*;
 use wasm_structs::WasmSaplingKeyError;
@@ -19,6 +21,7 @@ pub struct Key {
     spending_key: String,
     incoming_view_key: String,
     outgoing_view_key: String,
+    full_view_key: String,
     public_address: String,
 }
 
@@ -39,35 +42,40 @@ impl Key {
         self.outgoing_view_key.clone()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn full_view_key(&self) -> String {
+        self.full_view_key.clone()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn public_address(&self) -> String {
         self.public_address.clone()
     }
 }
 
-#[wasm_bindgen(js_name = "generateKey")]
-pub fn create_key_to_js() -> Key {
-    let hasher = sapling_bls12::SAPLING.clone();
-    let sapling_key = sapling_bls12::Key::generate_key(hasher);
+#[wasm_bindgen(catch, js_name = "generateKey")]
+pub fn create_key_to_js() -> Result<Key, JsValue> {
+    let sapling_key = sapling_bls12::Key::generate_key(sapling::sapling()?);
 
-    Key {
+    Ok(Key {
         spending_key: sapling_key.hex_spending_key(),
         incoming_view_key: sapling_key.incoming_view_key().hex_key(),
         outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
+        full_view_key: sapling_key.full_viewing_key().hex_key(),
         public_address: sapling_key.generate_public_address().hex_public_address(),
-    }
+    })
 }
 
 #[wasm_bindgen(catch, js_name = "generateNewPublicAddress")]
 pub fn create_new_public_key_to_js(private_key: &str) -> Result<Key, JsValue> {
-    let hasher = sapling_bls12::SAPLING.clone();
-    let sapling_key =
-        sapling_bls12::Key::from_hex(hasher, private_key).map_err(WasmSaplingKeyError)?;
+    let sapling_key = sapling_bls12::Key::from_hex(sapling::sapling()?, private_key)
+        .map_err(WasmSaplingKeyError)?;
 
     Ok(Key {
         spending_key: sapling_key.hex_spending_key(),
         incoming_view_key: sapling_key.incoming_view_key().hex_key(),
         outgoing_view_key: sapling_key.outgoing_view_key().hex_key(),
+        full_view_key: sapling_key.full_viewing_key().hex_key(),
         public_address: sapling_key.generate_public_address().hex_public_address(),
     })
 }
@@ -78,12 +86,14 @@ mod tests {
 
     #[test]
     fn test_create_new_public_key_to_js() {
-        let key1 = create_key_to_js();
+        sapling::load_test_params();
+        let key1 = create_key_to_js().unwrap();
         let key2 = create_new_public_key_to_js(&key1.spending_key).unwrap();
 
         assert_eq!(key1.spending_key(), key2.spending_key());
         assert_eq!(key1.incoming_view_key(), key2.incoming_view_key());
         assert_eq!(key1.outgoing_view_key(), key2.outgoing_view_key());
+        assert_eq!(key1.full_view_key(), key2.full_view_key());
 
         assert_ne!(key1.public_address(), key2.public_address());
     }
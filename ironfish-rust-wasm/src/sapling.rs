@@ -0,0 +1,42 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ironfish_rust::sapling_bls12::{self, Sapling};
+use std::{cell::RefCell, sync::Arc};
+use wasm_bindgen::prelude::*;
+
+use super::wasm_structs::{panic_hook, WasmSaplingParamsError};
+
+// The parameters are several megabytes, so they aren't compiled into the
+// wasm module. The JS side fetches them and hands them to `loadSaplingParams`
+// once, before calling anything that needs them.
+thread_local! {
+    static SAPLING: RefCell<Option<Arc<Sapling>>> = RefCell::new(None);
+}
+
+/// Load the serialized spend and output parameters. Fails if either doesn't
+/// match the published digest for its circuit.
+#[wasm_bindgen(catch, js_name = "loadSaplingParams")]
+pub fn load_sapling_params(spend_params: &[u8], output_params: &[u8]) -> Result<(), JsValue> {
+    panic_hook::set_once();
+
+    let sapling = sapling_bls12::load_from_readers(spend_params, output_params)
+        .map_err(WasmSaplingParamsError)?;
+    SAPLING.with(|cell| *cell.borrow_mut() = Some(Arc::new(sapling)));
+    Ok(())
+}
+
+/// Get the Sapling instance loaded by `loadSaplingParams`.
+pub(crate) fn sapling() -> Result<Arc<Sapling>, JsValue> {
+    SAPLING.with(|cell| cell.borrow().clone()).ok_or_else(|| {
+        js_sys::Error::new("loadSaplingParams must be called before using Sapling").into()
+    })
+}
+
+/// Use the parameters compiled into ironfish_rust for the tests, which are
+/// built with the `embedded-params` feature.
+#[cfg(test)]
+pub(crate) fn load_test_params() {
+    SAPLING.with(|cell| *cell.borrow_mut() = Some(sapling_bls12::SAPLING.clone()));
+}
@@ -8,6 +8,8 @@ pub struct WasmIoError(pub std::io::Error);
 pub struct WasmSaplingKeyError(pub SaplingKeyError);
 pub struct WasmSaplingProofError(pub SaplingProofError);
 pub struct WasmTransactionError(pub TransactionError);
+pub struct WasmSaplingParamsError(pub SaplingParamsError);
+pub struct WasmNoteError(pub NoteError);
 
 impl From<WasmIoError> for wasm_bindgen::JsValue {
     fn from(e: WasmIoError) -> Self {
@@ -32,3 +34,15 @@ impl From<WasmTransactionError> for wasm_bindgen::JsValue {
         js_sys::Error::new(&e.0.to_string()).into()
     }
 }
+
+impl From<WasmSaplingParamsError> for wasm_bindgen::JsValue {
+    fn from(e: WasmSaplingParamsError) -> Self {
+        js_sys::Error::new(&e.0.to_string()).into()
+    }
+}
+
+impl From<WasmNoteError> for wasm_bindgen::JsValue {
+    fn from(e: WasmNoteError) -> Self {
+        js_sys::Error::new(&e.0.to_string()).into()
+    }
+}
@@ -2,9 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use super::{panic_hook, WasmIoError, WasmSaplingKeyError};
+use super::{panic_hook, WasmIoError, WasmNoteError, WasmSaplingKeyError};
+use crate::sapling::sapling;
 use ironfish_rust::note::Memo;
-use ironfish_rust::sapling_bls12::{Key, Note, SAPLING};
+use ironfish_rust::sapling_bls12::{FullViewingKey, Key, Note};
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -18,10 +20,12 @@ impl WasmNote {
     pub fn new(owner: &str, value: u64, memo: &str) -> Result<WasmNote, JsValue> {
         panic_hook::set_once();
 
-        let owner_address = ironfish_rust::PublicAddress::from_hex(SAPLING.clone(), owner)
+        let sapling = sapling()?;
+        let owner_address = ironfish_rust::PublicAddress::from_hex(sapling.clone(), owner)
             .map_err(WasmSaplingKeyError)?;
+        let memo = Memo::try_from(memo).map_err(WasmNoteError)?;
         Ok(WasmNote {
-            note: Note::new(SAPLING.clone(), owner_address, value, Memo::from(memo)),
+            note: Note::new(sapling, owner_address, value, memo),
         })
     }
 
@@ -29,7 +33,7 @@ impl WasmNote {
     pub fn deserialize(bytes: &[u8]) -> Result<WasmNote, JsValue> {
         panic_hook::set_once();
 
-        let hasher = SAPLING.clone();
+        let hasher = sapling()?;
         let cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
         let note = Note::read(cursor, hasher).map_err(WasmSaplingKeyError)?;
         Ok(WasmNote { note })
@@ -65,7 +69,24 @@ impl WasmNote {
     #[wasm_bindgen]
     pub fn nullifier(&self, owner_private_key: &str, position: u64) -> Result<Vec<u8>, JsValue> {
         let private_key =
-            Key::from_hex(SAPLING.clone(), owner_private_key).map_err(WasmSaplingKeyError)?;
+            Key::from_hex(sapling()?, owner_private_key).map_err(WasmSaplingKeyError)?;
         Ok(self.note.nullifier(&private_key, position).to_vec())
     }
+
+    /// Compute the nullifier for this note, given the full viewing key of its
+    /// owner. Watch-only wallets use this to see when their notes are spent
+    /// without having the spending key.
+    #[wasm_bindgen(js_name = "nullifierFromViewKey")]
+    pub fn nullifier_from_view_key(
+        &self,
+        owner_view_key: &str,
+        position: u64,
+    ) -> Result<Vec<u8>, JsValue> {
+        let view_key =
+            FullViewingKey::from_hex(sapling()?, owner_view_key).map_err(WasmSaplingKeyError)?;
+        Ok(self
+            .note
+            .nullifier_from_view_key(&view_key, position)
+            .to_vec())
+    }
 }
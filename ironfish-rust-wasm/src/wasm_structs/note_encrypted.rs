@@ -7,6 +7,7 @@ use ironfish_rust::MerkleNote;
 use wasm_bindgen::prelude::*;
 
 use super::{panic_hook, WasmIoError, WasmNote, WasmSaplingKeyError};
+use crate::sapling::sapling;
 
 #[wasm_bindgen]
 pub struct WasmNoteEncrypted {
@@ -19,9 +20,9 @@ impl WasmNoteEncrypted {
     pub fn deserialize(bytes: &[u8]) -> Result<WasmNoteEncrypted, JsValue> {
         panic_hook::set_once();
 
-        let hasher = sapling_bls12::SAPLING.clone();
+        let hasher = sapling()?;
         let cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let note = MerkleNote::read(cursor, hasher).map_err(WasmIoError)?;
+        let note = MerkleNote::read(cursor, &hasher.jubjub).map_err(WasmIoError)?;
         Ok(WasmNoteEncrypted { note })
     }
 
@@ -61,7 +62,7 @@ impl WasmNoteEncrypted {
         let mut cursor: Vec<u8> = Vec::with_capacity(32);
 
         sapling_bls12::MerkleNoteHash::new(sapling_bls12::MerkleNoteHash::combine_hash(
-            &sapling_bls12::SAPLING.clone(),
+            &sapling()?,
             depth,
             &left_hash.0,
             &right_hash.0,
@@ -75,9 +76,8 @@ impl WasmNoteEncrypted {
     /// Returns undefined if the note was unable to be decrypted with the given key.
     #[wasm_bindgen(js_name = "decryptNoteForOwner")]
     pub fn decrypt_note_for_owner(&self, owner_hex_key: &str) -> Result<Option<WasmNote>, JsValue> {
-        let owner_view_key =
-            sapling_bls12::IncomingViewKey::from_hex(sapling_bls12::SAPLING.clone(), owner_hex_key)
-                .map_err(WasmSaplingKeyError)?;
+        let owner_view_key = sapling_bls12::IncomingViewKey::from_hex(sapling()?, owner_hex_key)
+            .map_err(WasmSaplingKeyError)?;
         Ok(match self.note.decrypt_note_for_owner(&owner_view_key) {
             Ok(n) => Some(WasmNote { note: { n } }),
             Err(_) => None,
@@ -90,11 +90,9 @@ impl WasmNoteEncrypted {
         &self,
         spender_hex_key: &str,
     ) -> Result<Option<WasmNote>, JsValue> {
-        let spender_view_key = sapling_bls12::OutgoingViewKey::from_hex(
-            sapling_bls12::SAPLING.clone(),
-            spender_hex_key,
-        )
-        .map_err(WasmSaplingKeyError)?;
+        let spender_view_key =
+            sapling_bls12::OutgoingViewKey::from_hex(sapling()?, spender_hex_key)
+                .map_err(WasmSaplingKeyError)?;
 
         Ok(
             match self.note.decrypt_note_for_spender(&spender_view_key) {
@@ -122,6 +120,7 @@ mod tests {
 
     #[test]
     fn test_merkle_notes_are_equal() {
+        crate::sapling::load_test_params();
         let spender_key: SaplingKey<Bls12> =
             SaplingKey::generate_key(sapling_bls12::SAPLING.clone());
         let receiver_key: SaplingKey<Bls12> =
@@ -131,7 +130,7 @@ mod tests {
             sapling_bls12::SAPLING.clone(),
             owner.clone(),
             42,
-            Memo([0; 32]),
+            Memo::default(),
         );
         let diffie_hellman_keys =
             owner.generate_diffie_hellman_keys(&sapling_bls12::SAPLING.jubjub);
@@ -160,6 +159,7 @@ mod tests {
 
     #[test]
     fn test_can_combine_merkle_note_hashes() {
+        crate::sapling::load_test_params();
         let arr: [u8; 32] = Default::default();
         let combined_hash = WasmNoteEncrypted::combine_hash(1, &arr, &arr).unwrap();
 
@@ -5,7 +5,7 @@
 use wasm_bindgen::prelude::*;
 
 use ironfish_rust::sapling_bls12::{
-    Key, ProposedTransaction, PublicAddress, SimpleTransaction, Transaction, SAPLING,
+    Key, ProposedTransaction, PublicAddress, SimpleTransaction, Transaction,
 };
 
 use super::errors::*;
@@ -13,6 +13,7 @@ use super::note::WasmNote;
 use super::panic_hook;
 use super::spend_proof::WasmSpendProof;
 use super::witness::JsWitness;
+use crate::sapling::sapling;
 
 #[wasm_bindgen]
 pub struct WasmTransactionPosted {
@@ -26,8 +27,8 @@ impl WasmTransactionPosted {
         panic_hook::set_once();
 
         let mut cursor: std::io::Cursor<&[u8]> = std::io::Cursor::new(bytes);
-        let transaction =
-            Transaction::read(SAPLING.clone(), &mut cursor).map_err(WasmTransactionError)?;
+        let transaction = Transaction::read(sapling()?.verifier().clone(), &mut cursor)
+            .map_err(WasmTransactionError)?;
         Ok(WasmTransactionPosted { transaction })
     }
 
@@ -110,19 +111,19 @@ pub struct WasmTransaction {
 #[wasm_bindgen]
 impl WasmTransaction {
     #[wasm_bindgen(constructor)]
-    pub fn new() -> WasmTransaction {
+    pub fn new() -> Result<WasmTransaction, JsValue> {
         panic_hook::set_once();
 
-        WasmTransaction {
-            transaction: ProposedTransaction::new(SAPLING.clone()),
-        }
+        Ok(WasmTransaction {
+            transaction: ProposedTransaction::new(sapling()?),
+        })
     }
 
     /// Create a proof of a new note owned by the recipient in this transaction.
     #[wasm_bindgen]
     pub fn receive(&mut self, spender_hex_key: &str, note: &WasmNote) -> Result<String, JsValue> {
         let spender_key =
-            Key::from_hex(SAPLING.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
+            Key::from_hex(sapling()?, spender_hex_key).map_err(WasmSaplingKeyError)?;
         self.transaction
             .receive(&spender_key, &note.note)
             .map_err(WasmSaplingProofError)?;
@@ -138,7 +139,7 @@ impl WasmTransaction {
         witness: &JsWitness,
     ) -> Result<String, JsValue> {
         let spender_key =
-            Key::from_hex(SAPLING.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
+            Key::from_hex(sapling()?, spender_hex_key).map_err(WasmSaplingKeyError)?;
         self.transaction
             .spend(spender_key, &note.note, witness)
             .map_err(WasmSaplingProofError)?;
@@ -176,12 +177,11 @@ impl WasmTransaction {
         change_goes_to: Option<String>,
         intended_transaction_fee: u64,
     ) -> Result<WasmTransactionPosted, JsValue> {
+        let sapling = sapling()?;
         let spender_key =
-            Key::from_hex(SAPLING.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
+            Key::from_hex(sapling.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
         let change_key = match change_goes_to {
-            Some(s) => {
-                Some(PublicAddress::from_hex(SAPLING.clone(), &s).map_err(WasmSaplingKeyError)?)
-            }
+            Some(s) => Some(PublicAddress::from_hex(sapling, &s).map_err(WasmSaplingKeyError)?),
             None => None,
         };
 
@@ -202,12 +202,6 @@ impl WasmTransaction {
     }
 }
 
-impl Default for WasmTransaction {
-    fn default() -> Self {
-        WasmTransaction::new()
-    }
-}
-
 #[wasm_bindgen]
 pub struct WasmSimpleTransaction {
     transaction: SimpleTransaction,
@@ -222,14 +216,11 @@ impl WasmSimpleTransaction {
     ) -> Result<WasmSimpleTransaction, JsValue> {
         panic_hook::set_once();
 
+        let sapling = sapling()?;
         let spender_key =
-            Key::from_hex(SAPLING.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
+            Key::from_hex(sapling.clone(), spender_hex_key).map_err(WasmSaplingKeyError)?;
         Ok(WasmSimpleTransaction {
-            transaction: SimpleTransaction::new(
-                SAPLING.clone(),
-                spender_key,
-                intended_transaction_fee,
-            ),
+            transaction: SimpleTransaction::new(sapling, spender_key, intended_transaction_fee),
         })
     }
 
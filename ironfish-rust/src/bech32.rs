@@ -0,0 +1,269 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Bech32m encoding (BIP-350) for keys and addresses.
+//!
+//! Bech32m strings are a human-readable prefix, the separator `1`, and the
+//! data in a 32 character alphabet followed by a six character checksum.
+//! For strings of up to 90 characters, such as addresses, the checksum is
+//! guaranteed to detect up to four wrong characters, and a single wrong
+//! character can be located, so decoding errors point at the character that
+//! needs to be fixed.
+//!
+//! BIP-350 limits strings to 90 characters, but some keys are longer: a full
+//! viewing key is about 168. Those are still encoded the same way, and a
+//! mistyped one is still rejected with overwhelming probability, but neither
+//! guarantee holds for them, so the wrong character isn't located.
+
+use crate::errors::Bech32Error;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const SEPARATOR: char = '1';
+const CHECKSUM_LENGTH: usize = 6;
+/// Longest string the checksum's guarantees hold for.
+const GUARANTEED_LENGTH: usize = 90;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+const GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+/// Encode the data with the given human-readable prefix, which must be
+/// lowercase.
+pub(crate) fn encode(hrp: &str, data: &[u8]) -> String {
+    let mut values = convert_bits(data, 8, 5, true).expect("padding is allowed when encoding");
+    let checksum = create_checksum(hrp, &values);
+    values.extend_from_slice(&checksum);
+
+    let mut encoded = String::with_capacity(hrp.len() + 1 + values.len());
+    encoded.push_str(hrp);
+    encoded.push(SEPARATOR);
+    for value in values {
+        encoded.push(CHARSET[value as usize] as char);
+    }
+    encoded
+}
+
+/// Decode a string that is expected to have the given human-readable prefix.
+///
+/// Errors that can be pinned to one character report its position in the
+/// string, counting from zero.
+pub(crate) fn decode(hrp: &str, value: &str) -> Result<Vec<u8>, Bech32Error> {
+    if !value.is_ascii() {
+        let position = value.chars().position(|c| !c.is_ascii()).unwrap_or(0);
+        return Err(Bech32Error::InvalidCharacter(position));
+    }
+
+    let first_lowercase = value.bytes().position(|c| c.is_ascii_lowercase());
+    let first_uppercase = value.bytes().position(|c| c.is_ascii_uppercase());
+    if let (Some(lower), Some(upper)) = (first_lowercase, first_uppercase) {
+        return Err(Bech32Error::MixedCase(std::cmp::max(lower, upper)));
+    }
+    let value = value.to_ascii_lowercase();
+
+    let separator = value
+        .rfind(SEPARATOR)
+        .ok_or(Bech32Error::MissingSeparator)?;
+    if &value[..separator] != hrp {
+        return Err(Bech32Error::InvalidPrefix);
+    }
+    let data_start = separator + 1;
+    if value.len() < data_start + CHECKSUM_LENGTH {
+        return Err(Bech32Error::InvalidLength);
+    }
+
+    let mut values = Vec::with_capacity(value.len() - data_start);
+    for (offset, c) in value[data_start..].bytes().enumerate() {
+        match CHARSET.iter().position(|x| *x == c) {
+            Some(v) => values.push(v as u8),
+            None => return Err(Bech32Error::InvalidCharacter(data_start + offset)),
+        }
+    }
+
+    if !verify_checksum(hrp, &values) {
+        let position = if value.len() <= GUARANTEED_LENGTH {
+            locate_error(hrp, &values).map(|offset| data_start + offset)
+        } else {
+            None
+        };
+        return Err(Bech32Error::InvalidChecksum(position));
+    }
+
+    values.truncate(values.len() - CHECKSUM_LENGTH);
+    convert_bits(&values, 5, 8, false).ok_or(Bech32Error::InvalidPadding)
+}
+
+/// Find the single character that, when replaced, makes the checksum valid.
+///
+/// Any two valid strings of up to `GUARANTEED_LENGTH` characters differ in
+/// at least five characters, so there is at most one such position. Returns
+/// None if the string has more than one error.
+fn locate_error(hrp: &str, values: &[u8]) -> Option<usize> {
+    let mut candidate = values.to_vec();
+    for position in 0..values.len() {
+        for replacement in 0..32 {
+            if replacement == values[position] {
+                continue;
+            }
+            candidate[position] = replacement;
+            if verify_checksum(hrp, &candidate) {
+                return Some(position);
+            }
+        }
+        candidate[position] = values[position];
+    }
+    None
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x01ff_ffff) << 5 ^ u32::from(*value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn expand_hrp(hrp: &str) -> Vec<u8> {
+    let mut expanded = Vec::with_capacity(hrp.len() * 2 + 1);
+    expanded.extend(hrp.bytes().map(|c| c >> 5));
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|c| c & 31));
+    expanded
+}
+
+fn verify_checksum(hrp: &str, values: &[u8]) -> bool {
+    let mut checked = expand_hrp(hrp);
+    checked.extend_from_slice(values);
+    polymod(&checked) == BECH32M_CONST
+}
+
+fn create_checksum(hrp: &str, values: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let mut checked = expand_hrp(hrp);
+    checked.extend_from_slice(values);
+    checked.extend_from_slice(&[0; CHECKSUM_LENGTH]);
+    let remainder = polymod(&checked) ^ BECH32M_CONST;
+
+    let mut checksum = [0; CHECKSUM_LENGTH];
+    for (i, value) in checksum.iter_mut().enumerate() {
+        *value = ((remainder >> (5 * (5 - i))) & 31) as u8;
+    }
+    checksum
+}
+
+/// Regroup the bits of `data` from `from` bits per element to `to` bits per
+/// element. When not padding, leftover bits must be zero and fewer than
+/// `from`, or None is returned.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value: u32 = (1 << to) - 1;
+    let mut converted = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for value in data {
+        accumulator = (accumulator << from) | u32::from(*value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            converted.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            converted.push(((accumulator << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || ((accumulator << (to - bits)) & max_value) != 0 {
+        return None;
+    }
+    Some(converted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode, verify_checksum, CHARSET};
+    use crate::errors::Bech32Error;
+
+    #[test]
+    fn test_bip350_vectors() {
+        // Valid Bech32m strings from BIP-350
+        for valid in &[
+            "a1lqfn3a",
+            "A1LQFN3A",
+            "?1v759aa",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "11llllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllludsr8",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+        ] {
+            let separator = valid.rfind('1').unwrap();
+            let hrp = valid[..separator].to_ascii_lowercase();
+            let values: Vec<u8> = valid[separator + 1..]
+                .to_ascii_lowercase()
+                .bytes()
+                .map(|c| CHARSET.iter().position(|x| *x == c).unwrap() as u8)
+                .collect();
+            assert!(verify_checksum(&hrp, &values), "{}", valid);
+        }
+
+        // The data part of this vector is every character of the alphabet,
+        // from the last to the first, which is exactly 20 bytes
+        assert_eq!(
+            decode("abcdef", "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx").unwrap(),
+            vec![
+                255, 187, 205, 235, 56, 189, 171, 73, 202, 48, 123, 154, 197, 169, 40, 57, 138, 65,
+                136, 32
+            ]
+        );
+        assert!(matches!(
+            decode("abcdef", "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryq"),
+            Err(Bech32Error::InvalidChecksum(Some(44)))
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_and_errors() {
+        let data: Vec<u8> = (0..43).collect();
+        let encoded = encode("iron", &data);
+        assert!(encoded.starts_with("iron1"));
+        assert_eq!(decode("iron", &encoded).unwrap(), data);
+        assert_eq!(decode("iron", &encoded.to_uppercase()).unwrap(), data);
+
+        assert!(matches!(
+            decode("tiron", &encoded),
+            Err(Bech32Error::InvalidPrefix)
+        ));
+
+        let mut typo = encoded.clone().into_bytes();
+        typo[20] = if typo[20] == b'q' { b'p' } else { b'q' };
+        let typo = String::from_utf8(typo).unwrap();
+        assert!(matches!(
+            decode("iron", &typo),
+            Err(Bech32Error::InvalidChecksum(Some(20)))
+        ));
+
+        let mut invalid = encoded.clone().into_bytes();
+        invalid[12] = b'b';
+        let invalid = String::from_utf8(invalid).unwrap();
+        assert!(matches!(
+            decode("iron", &invalid),
+            Err(Bech32Error::InvalidCharacter(12))
+        ));
+
+        // The prefix is always letters, so this is always an uppercase letter
+        let mut mixed = encoded.into_bytes();
+        mixed[2] = mixed[2].to_ascii_uppercase();
+        let mixed = String::from_utf8(mixed).unwrap();
+        assert!(matches!(
+            decode("iron", &mixed),
+            Err(Bech32Error::MixedCase(2))
+        ));
+    }
+}
@@ -19,6 +19,13 @@ pub enum SaplingKeyError {
     DiversificationError,
     InvalidLanguageEncoding,
     InvalidWord,
+    InvalidSeed,
+    InvalidChildIndex,
+    Bech32Error(Bech32Error),
+    InvalidKeystore,
+    IncorrectPassword,
+    InvalidMnemonicChecksum,
+    InvalidMnemonicLength,
 }
 
 impl fmt::Display for SaplingKeyError {
@@ -35,6 +42,36 @@ impl From<io::Error> for SaplingKeyError {
     }
 }
 
+impl From<Bech32Error> for SaplingKeyError {
+    fn from(e: Bech32Error) -> SaplingKeyError {
+        SaplingKeyError::Bech32Error(e)
+    }
+}
+
+/// Errors raised when decoding a Bech32m string. Where the error can be
+/// attributed to a single character, its position in the string is given,
+/// counting from zero.
+#[derive(Debug)]
+pub enum Bech32Error {
+    MissingSeparator,
+    InvalidPrefix,
+    InvalidLength,
+    MixedCase(usize),
+    InvalidCharacter(usize),
+    /// The checksum doesn't match. Contains the position of the wrong
+    /// character if exactly one character is wrong.
+    InvalidChecksum(Option<usize>),
+    InvalidPadding,
+}
+
+impl fmt::Display for Bech32Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for Bech32Error {}
+
 /// Error raised if proving fails for some reason
 #[derive(Debug)]
 pub enum SaplingProofError {
@@ -73,6 +110,28 @@ impl From<io::Error> for SaplingProofError {
     }
 }
 
+/// Errors raised when loading the sapling parameters
+#[derive(Debug)]
+pub enum SaplingParamsError {
+    IoError(io::Error),
+    SpendParamsHashMismatch,
+    ReceiptParamsHashMismatch,
+}
+
+impl fmt::Display for SaplingParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for SaplingParamsError {}
+
+impl From<io::Error> for SaplingParamsError {
+    fn from(e: io::Error) -> SaplingParamsError {
+        SaplingParamsError::IoError(e)
+    }
+}
+
 /// Errors raised when constructing a transaction
 #[derive(Debug)]
 pub enum TransactionError {
@@ -115,6 +174,9 @@ pub enum NoteError {
     RandomnessError,
     KeyError,
     InvalidCommitment,
+    MemoTooLong,
+    InvalidMemo,
+    InvalidVersion,
 }
 
 impl fmt::Display for NoteError {
@@ -136,3 +198,96 @@ impl From<SaplingKeyError> for NoteError {
         NoteError::KeyError
     }
 }
+
+/// Errors raised when appending to or querying a note commitment tree
+#[derive(Debug)]
+pub enum MerkleTreeError {
+    TreeFull,
+    InvalidTreeSize,
+    InvalidPosition,
+    MissingNode,
+    IoError(io::Error),
+}
+
+impl fmt::Display for MerkleTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for MerkleTreeError {}
+
+impl From<io::Error> for MerkleTreeError {
+    fn from(e: io::Error) -> MerkleTreeError {
+        MerkleTreeError::IoError(e)
+    }
+}
+
+/// Errors raised when verifying a batch of transactions, identifying the
+/// first item in the batch that failed. `InvalidProofBatch` and
+/// `InvalidSignatureBatch` mean the batch failed even though every item in it
+/// passed on its own.
+#[derive(Debug)]
+pub enum BatchVerificationError {
+    InvalidSpendProof { transaction: usize, spend: usize },
+    InvalidReceiptProof { transaction: usize, receipt: usize },
+    InvalidSpendSignature { transaction: usize, spend: usize },
+    InvalidBindingSignature { transaction: usize },
+    InvalidProofBatch,
+    InvalidSignatureBatch,
+}
+
+impl fmt::Display for BatchVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for BatchVerificationError {}
+
+impl From<BatchVerificationError> for TransactionError {
+    fn from(_e: BatchVerificationError) -> TransactionError {
+        TransactionError::VerificationFailed
+    }
+}
+
+/// Errors raised when creating, reading, or verifying a payment disclosure
+#[derive(Debug)]
+pub enum PaymentDisclosureError {
+    InvalidReceiptIndex,
+    InvalidTransaction,
+    InvalidSignature,
+    InvalidEphemeralKey,
+    InvalidRecipient,
+    InvalidMessage,
+    NotPosted,
+    IoError(io::Error),
+    KeyError(SaplingKeyError),
+    NoteError(NoteError),
+}
+
+impl fmt::Display for PaymentDisclosureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for PaymentDisclosureError {}
+
+impl From<io::Error> for PaymentDisclosureError {
+    fn from(e: io::Error) -> PaymentDisclosureError {
+        PaymentDisclosureError::IoError(e)
+    }
+}
+
+impl From<SaplingKeyError> for PaymentDisclosureError {
+    fn from(e: SaplingKeyError) -> PaymentDisclosureError {
+        PaymentDisclosureError::KeyError(e)
+    }
+}
+
+impl From<NoteError> for PaymentDisclosureError {
+    fn from(e: NoteError) -> PaymentDisclosureError {
+        PaymentDisclosureError::NoteError(e)
+    }
+}
@@ -0,0 +1,230 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::SaplingKey;
+use crate::{errors, Sapling};
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{io, sync::Arc};
+use zcash_primitives::jubjub::JubjubEngine;
+use zeroize::Zeroize;
+
+const MASTER_KEY_PERSONALIZATION: &[u8; 16] = b"IronFish_HD_Seed";
+const CHILD_KEY_PERSONALIZATION: &[u8; 16] = b"IronFish_HD_Chld";
+const FINGERPRINT_PERSONALIZATION: &[u8; 16] = b"IronFish_FVKFpnt";
+
+/// Child indexes at or above this value are hardened. Only hardened
+/// derivation is supported, so every derived index has this bit set.
+pub const HARDENED_KEY_START: u32 = 1 << 31;
+
+/// Seeds must be between 32 and 252 bytes long, as in ZIP-32.
+const MIN_SEED_LENGTH: usize = 32;
+const MAX_SEED_LENGTH: usize = 252;
+
+/// A SaplingKey that can derive child keys, so that many accounts can be
+/// recovered from a single seed.
+///
+/// This follows the structure of ZIP-32: a master key and chain code are
+/// derived from the seed, and each child is derived from its parent's key
+/// and chain code with a pseudorandom function. Only hardened derivation is
+/// supported, since a SaplingKey is expanded from a single 32 byte spending
+/// key and there is no way to derive children from the viewing keys alone.
+///
+/// The child's spending key and chain code are the two halves of
+/// `BLAKE2b-512(key: chain_code, 0x11 || spending_key || index)`.
+#[derive(Clone)]
+pub struct ExtendedSpendingKey<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Number of derivation steps from the master key.
+    depth: u8,
+
+    /// First four bytes of the parent's full viewing key fingerprint, or zero
+    /// for the master key.
+    parent_fingerprint: [u8; 4],
+
+    /// Index this key was derived at, including the hardened bit. Zero for
+    /// the master key.
+    child_index: u32,
+
+    /// Extra entropy that is combined with the spending key to derive
+    /// children.
+    chain_code: [u8; 32],
+
+    /// The key itself.
+    key: SaplingKey<J>,
+}
+
+/// The chain code is as secret as the key itself, since it is all that's
+/// needed to derive the children from the key.
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Drop for ExtendedSpendingKey<J> {
+    fn drop(&mut self) {
+        self.chain_code.zeroize();
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> ExtendedSpendingKey<J> {
+    /// Derive the master key from a seed, such as the one generated from a
+    /// BIP-39 mnemonic.
+    ///
+    /// Fails with `InvalidSeed` if the seed is too short or too long. Like
+    /// `derive_child`, it can also fail with `InvalidViewingKey`, with
+    /// negligible probability.
+    pub fn master(sapling: Arc<Sapling<J>>, seed: &[u8]) -> Result<Self, errors::SaplingKeyError> {
+        if seed.len() < MIN_SEED_LENGTH || seed.len() > MAX_SEED_LENGTH {
+            return Err(errors::SaplingKeyError::InvalidSeed);
+        }
+
+        let hash = Blake2b::new()
+            .hash_length(64)
+            .personal(MASTER_KEY_PERSONALIZATION)
+            .hash(seed);
+        let (spending_key, chain_code) = split_hash(hash.as_bytes());
+
+        Ok(ExtendedSpendingKey {
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_index: 0,
+            chain_code,
+            key: SaplingKey::new(sapling, spending_key)?,
+        })
+    }
+
+    /// Derive the hardened child at `index`, which must be below
+    /// `HARDENED_KEY_START`.
+    ///
+    /// Unlike ZIP-32 derivation, this can fail with `InvalidViewingKey`: the
+    /// derived spending key is rejected if its incoming view key is zero,
+    /// which `SaplingKey::new` doesn't allow. That happens with probability
+    /// about 2^-251, so in practice it never does, but callers that want to
+    /// handle it can move on to the next index, as BIP-32 does for invalid
+    /// children.
+    pub fn derive_child(&self, index: u32) -> Result<Self, errors::SaplingKeyError> {
+        if index >= HARDENED_KEY_START || self.depth == u8::MAX {
+            return Err(errors::SaplingKeyError::InvalidChildIndex);
+        }
+        let child_index = index | HARDENED_KEY_START;
+
+        let mut hasher = Blake2b::new()
+            .hash_length(64)
+            .key(&self.chain_code)
+            .personal(CHILD_KEY_PERSONALIZATION)
+            .to_state();
+        hasher.update(&[0x11]);
+        hasher.update(&self.key.spending_key);
+        hasher.update(&child_index.to_le_bytes());
+        let (spending_key, chain_code) = split_hash(hasher.finalize().as_bytes());
+
+        Ok(ExtendedSpendingKey {
+            depth: self.depth + 1,
+            parent_fingerprint: self.fingerprint(),
+            child_index,
+            chain_code,
+            key: SaplingKey::new(self.key.sapling.clone(), spending_key)?,
+        })
+    }
+
+    /// Derive the key at the end of a path of hardened indexes, starting from
+    /// this key. An account would typically be derived from the master key
+    /// with a path such as `[32, coin_type, account]`.
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, errors::SaplingKeyError> {
+        let mut key = self.clone();
+        for index in path {
+            key = key.derive_child(*index)?;
+        }
+        Ok(key)
+    }
+
+    /// Load an extended key from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(
+        sapling: Arc<Sapling<J>>,
+        mut reader: R,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let depth = reader.read_u8()?;
+        let mut parent_fingerprint = [0; 4];
+        reader.read_exact(&mut parent_fingerprint)?;
+        let child_index = reader.read_u32::<LittleEndian>()?;
+        let mut chain_code = [0; 32];
+        reader.read_exact(&mut chain_code)?;
+        let key = SaplingKey::read(sapling, &mut reader)?;
+
+        Ok(ExtendedSpendingKey {
+            depth,
+            parent_fingerprint,
+            child_index,
+            chain_code,
+            key,
+        })
+    }
+
+    /// Write a bytes representation of this key to the provided stream. The
+    /// fields follow a ZIP-32-style layout, but the key is not a ZIP-32
+    /// extended spending key: it holds a 32 byte spending key instead of the
+    /// expanded ask, nsk and ovk, so ZIP-32 implementations can't read it.
+    /// It is laid out as:
+    ///
+    ///  *  depth (1 byte)
+    ///  *  parent fingerprint (4 bytes)
+    ///  *  child index (4 bytes, little endian)
+    ///  *  chain code (32 bytes)
+    ///  *  spending key (32 bytes)
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u8(self.depth)?;
+        writer.write_all(&self.parent_fingerprint)?;
+        writer.write_u32::<LittleEndian>(self.child_index)?;
+        writer.write_all(&self.chain_code)?;
+        self.key.write(&mut writer)
+    }
+
+    /// Number of derivation steps from the master key.
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Index this key was derived at, including the hardened bit.
+    pub fn child_index(&self) -> u32 {
+        self.child_index
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    /// Retrieve the SaplingKey that this extended key wraps.
+    pub fn key(&self) -> &SaplingKey<J> {
+        &self.key
+    }
+
+    /// Identify this key by a hash of its full viewing key, so that children
+    /// can record their parent without revealing it.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .personal(FINGERPRINT_PERSONALIZATION)
+            .to_state();
+        let mut point_bytes = [0; 32];
+        self.key
+            .authorizing_key
+            .write(&mut point_bytes[..])
+            .expect("should be able to write authorizing key");
+        hasher.update(&point_bytes);
+        self.key
+            .nullifier_deriving_key
+            .write(&mut point_bytes[..])
+            .expect("should be able to write nullifier deriving key");
+        hasher.update(&point_bytes);
+        hasher.update(&self.key.outgoing_viewing_key.view_key);
+
+        let mut fingerprint = [0; 4];
+        fingerprint.copy_from_slice(&hasher.finalize().as_bytes()[..4]);
+        fingerprint
+    }
+}
+
+/// Split a 64 byte hash into a spending key and a chain code.
+fn split_hash(hash: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut spending_key = [0; 32];
+    let mut chain_code = [0; 32];
+    spending_key.copy_from_slice(&hash[..32]);
+    chain_code.copy_from_slice(&hash[32..64]);
+    (spending_key, chain_code)
+}
@@ -0,0 +1,139 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Password-protected storage for spending keys.
+//!
+//! A keystore holds the spending key encrypted with ChaCha20Poly1305, under a
+//! key derived from the password with scrypt. It is laid out as:
+//!
+//!  *  version (1 byte)
+//!  *  scrypt `log_n` (1 byte), `r` and `p` (4 bytes each, little endian)
+//!  *  salt (32 bytes)
+//!  *  encrypted spending key and MAC (48 bytes)
+//!
+//! Every keystore has a fresh random salt, so the derived key is never reused
+//! and the fixed nonce of the aead facade is safe. Changing any of the header
+//! fields changes the derived key, so a tampered keystore fails to decrypt
+//! just like a wrong password does.
+
+use super::SaplingKey;
+use crate::{errors, serializing::aead, Sapling};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crypto::scrypt::{scrypt, ScryptParams};
+use rand::random;
+use std::{io::Read, sync::Arc};
+use zcash_primitives::jubjub::JubjubEngine;
+use zeroize::Zeroize;
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_SIZE: usize = 32;
+const HEADER_SIZE: usize = 1 + 1 + 4 + 4 + SALT_SIZE;
+pub const KEYSTORE_SIZE: usize = HEADER_SIZE + 32 + aead::MAC_SIZE;
+
+/// Scrypt cost used for new keystores: 2^15 iterations with 32 MiB of memory.
+const DEFAULT_LOG_N: u8 = 15;
+const DEFAULT_R: u32 = 8;
+const DEFAULT_P: u32 = 1;
+
+/// Upper bounds on the scrypt cost accepted when reading a keystore, so that a
+/// malicious file can't make us allocate more than 256 MiB (`128 * r * 2^log_n`
+/// bytes), eight times the default, or do more than 32 times the default work.
+/// Scrypt also requires `log_n < 16 * r`.
+const MAX_LOG_N: u8 = 18;
+const MAX_R: u32 = 8;
+const MAX_P: u32 = 4;
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
+    /// Encrypt the spending key with the given password, so it can be stored
+    /// at rest. Use `from_keystore` with the same password to load it.
+    pub fn to_keystore(&self, password: &str) -> Vec<u8> {
+        self.to_keystore_with_cost(password, DEFAULT_LOG_N, DEFAULT_R, DEFAULT_P)
+    }
+
+    /// Load a key from a keystore created with `to_keystore`.
+    ///
+    /// Fails with `IncorrectPassword` if the password is wrong or the
+    /// keystore has been modified; the two can't be told apart.
+    pub fn from_keystore(
+        sapling: Arc<Sapling<J>>,
+        password: &str,
+        keystore: &[u8],
+    ) -> Result<Self, errors::SaplingKeyError> {
+        if keystore.len() != KEYSTORE_SIZE {
+            return Err(errors::SaplingKeyError::InvalidKeystore);
+        }
+
+        let mut reader = keystore;
+        let version = reader.read_u8()?;
+        let log_n = reader.read_u8()?;
+        let r = reader.read_u32::<LittleEndian>()?;
+        let p = reader.read_u32::<LittleEndian>()?;
+        let mut salt = [0; SALT_SIZE];
+        reader.read_exact(&mut salt)?;
+
+        if version != KEYSTORE_VERSION
+            || log_n == 0
+            || log_n > MAX_LOG_N
+            || r == 0
+            || r > MAX_R
+            || p == 0
+            || p > MAX_P
+            || u32::from(log_n) >= 16 * r
+        {
+            return Err(errors::SaplingKeyError::InvalidKeystore);
+        }
+
+        let mut encryption_key = derive_key(password, &salt, log_n, r, p);
+        let mut spending_key = [0; 32];
+        let decrypted = aead::decrypt(&encryption_key, reader, &mut spending_key);
+        encryption_key.zeroize();
+        decrypted.map_err(|_| errors::SaplingKeyError::IncorrectPassword)?;
+
+        let key = Self::new(sapling, spending_key);
+        spending_key.zeroize();
+        key
+    }
+
+    /// Encrypt the spending key with a specific scrypt cost. Lets tests use a
+    /// cheaper key derivation than `to_keystore`.
+    pub(super) fn to_keystore_with_cost(
+        &self,
+        password: &str,
+        log_n: u8,
+        r: u32,
+        p: u32,
+    ) -> Vec<u8> {
+        let salt: [u8; SALT_SIZE] = random();
+        let mut encryption_key = derive_key(password, &salt, log_n, r, p);
+
+        let mut keystore = Vec::with_capacity(KEYSTORE_SIZE);
+        keystore.push(KEYSTORE_VERSION);
+        keystore.push(log_n);
+        keystore
+            .write_u32::<LittleEndian>(r)
+            .expect("should be able to write to a vector");
+        keystore
+            .write_u32::<LittleEndian>(p)
+            .expect("should be able to write to a vector");
+        keystore.extend_from_slice(&salt);
+
+        let mut encrypted_key = [0; 32 + aead::MAC_SIZE];
+        aead::encrypt(&encryption_key, &self.spending_key, &mut encrypted_key);
+        encryption_key.zeroize();
+        keystore.extend_from_slice(&encrypted_key);
+        keystore
+    }
+}
+
+/// Stretch the password into a 32 byte encryption key.
+fn derive_key(password: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> [u8; 32] {
+    let mut key = [0; 32];
+    scrypt(
+        password.as_bytes(),
+        salt,
+        &ScryptParams::new(log_n, r, p),
+        &mut key,
+    );
+    key
+}
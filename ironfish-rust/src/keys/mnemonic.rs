@@ -0,0 +1,70 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::SaplingKey;
+use crate::{errors::SaplingKeyError, Sapling};
+use bip39::{ErrorKind, Language, Mnemonic, MnemonicType, Seed};
+use std::sync::Arc;
+use zcash_primitives::jubjub::JubjubEngine;
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
+    /// Generate a new random 24 word BIP-39 phrase, for use with
+    /// `from_mnemonic`.
+    pub fn generate_mnemonic(language_code: &str) -> Result<String, SaplingKeyError> {
+        let language = Language::from_language_code(language_code)
+            .ok_or(SaplingKeyError::InvalidLanguageEncoding)?;
+        Ok(Mnemonic::new(MnemonicType::Words24, language)
+            .phrase()
+            .to_string())
+    }
+
+    /// Derive a key from a BIP-39 phrase of any standard length, following a
+    /// path of hardened child indexes from the master key.
+    ///
+    /// Unlike `from_words`, which reads the key directly from the words, this
+    /// turns the phrase into a seed the way BIP-39 specifies, so the optional
+    /// passphrase can be used, and derives the key from the seed as in
+    /// `from_seed`.
+    pub fn from_mnemonic(
+        sapling: Arc<Sapling<J>>,
+        language_code: &str,
+        phrase: &str,
+        passphrase: Option<&str>,
+        path: &[u32],
+    ) -> Result<Self, SaplingKeyError> {
+        let mnemonic = parse_mnemonic(language_code, phrase)?;
+        let seed = Seed::new(&mnemonic, passphrase.unwrap_or(""));
+        Self::from_seed(sapling, seed.as_bytes(), path)
+    }
+}
+
+/// Parse a BIP-39 phrase, with a separate error for each way it can be wrong.
+pub(super) fn parse_mnemonic(
+    language_code: &str,
+    phrase: &str,
+) -> Result<Mnemonic, SaplingKeyError> {
+    let language = Language::from_language_code(language_code)
+        .ok_or(SaplingKeyError::InvalidLanguageEncoding)?;
+    Mnemonic::from_phrase(phrase, language).map_err(|e| match e.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::InvalidChecksum) => SaplingKeyError::InvalidMnemonicChecksum,
+        Some(ErrorKind::InvalidWord) => SaplingKeyError::InvalidWord,
+        _ => SaplingKeyError::InvalidMnemonicLength,
+    })
+}
+
+/// Read the 32 bytes of entropy encoded by a 24 word phrase. Keys are encoded
+/// directly as words this way, so shorter phrases can't hold one.
+pub(super) fn words_to_bytes(
+    language_code: &str,
+    phrase: &str,
+) -> Result<[u8; 32], SaplingKeyError> {
+    let mnemonic = parse_mnemonic(language_code, phrase)?;
+    let entropy = mnemonic.entropy();
+    if entropy.len() != 32 {
+        return Err(SaplingKeyError::InvalidMnemonicLength);
+    }
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(entropy);
+    Ok(bytes)
+}
@@ -2,9 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use super::bech32;
 use super::errors;
 use super::serializing::{
-    bytes_to_hex, hex_to_bytes, point_to_bytes, read_scalar, scalar_to_bytes,
+    bytes_to_hex, hex_to_bytes, point_to_bytes, read_scalar, scalar_to_bytes, zeroize_scalar,
 };
 use super::Sapling;
 use bip39::{Language, Mnemonic};
@@ -14,12 +15,21 @@ use rand::prelude::*;
 // use rand_core::{OsRng, RngCore};
 use zcash_primitives::constants::CRH_IVK_PERSONALIZATION;
 
-use std::{io, sync::Arc};
+use std::{fmt, io, sync::Arc};
 use zcash_primitives::jubjub::{
     edwards, FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder, ToUniform,
 };
-use zcash_primitives::primitives::{ProofGenerationKey, ViewingKey};
-
+use zcash_primitives::primitives::ProofGenerationKey;
+use zeroize::Zeroize;
+
+mod extended;
+pub use extended::*;
+mod keystore;
+pub use keystore::*;
+mod mnemonic;
+use mnemonic::words_to_bytes;
+mod network;
+pub use network::*;
 mod public_address;
 pub use public_address::*;
 mod view_keys;
@@ -36,6 +46,10 @@ const EXPANDED_SPEND_BLAKE2_KEY: &[u8; 16] = b"Beanstalk Money ";
 /// While the key parts are all represented as 256 bit keys to the outside
 /// world, inside the API they map to Edwards points or scalar values
 /// on the JubJub curve.
+///
+/// Keys are cloned wherever a transaction takes ownership of the key that
+/// spends a note. Every clone zeroizes its own copy of the secret parts when
+/// it is dropped, so cloning doesn't leave copies behind in memory.
 #[derive(Clone)]
 pub struct SaplingKey<J: JubjubEngine + pairing::MultiMillerLoop> {
     pub(crate) sapling: Arc<Sapling<J>>,
@@ -79,6 +93,28 @@ pub struct SaplingKey<J: JubjubEngine + pairing::MultiMillerLoop> {
     pub(crate) incoming_viewing_key: IncomingViewKey<J>,
 }
 
+/// Clear the secret parts of the key from memory when it is dropped, so they
+/// don't linger in freed memory or end up in a core dump.
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Drop for SaplingKey<J> {
+    fn drop(&mut self) {
+        self.spending_key.zeroize();
+        zeroize_scalar(&mut self.spend_authorizing_key);
+        zeroize_scalar(&mut self.proof_authorizing_key);
+    }
+}
+
+/// Keys are printed without any of their secret parts, so they can't leak
+/// into logs.
+impl<J: JubjubEngine + pairing::MultiMillerLoop> fmt::Debug for SaplingKey<J> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SaplingKey")
+            .field("spending_key", &"<redacted>")
+            .field("spend_authorizing_key", &"<redacted>")
+            .field("proof_authorizing_key", &"<redacted>")
+            .finish()
+    }
+}
+
 impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
     /// Construct a new key from an array of bytes
     pub fn new(
@@ -147,20 +183,25 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
         }
     }
 
-    /// Load a key from a string of words to be decoded into bytes.
+    /// Load a key from a string of words to be decoded into bytes, as
+    /// produced by `words_spending_key`. The phrase must have 24 words.
     pub fn from_words(
         sapling: Arc<Sapling<J>>,
         language_code: &str,
         value: String,
     ) -> Result<Self, errors::SaplingKeyError> {
-        let language = Language::from_language_code(language_code)
-            .ok_or(errors::SaplingKeyError::InvalidLanguageEncoding)?;
-        let mnemonic = Mnemonic::from_phrase(&value, language)
-            .map_err(|_| errors::SaplingKeyError::InvalidPaymentAddress)?;
-        let bytes = mnemonic.entropy();
-        let mut byte_arr = [0; 32];
-        byte_arr.clone_from_slice(&bytes[0..32]);
-        Self::new(sapling, byte_arr)
+        Self::new(sapling, words_to_bytes(language_code, &value)?)
+    }
+
+    /// Derive a key from a seed, following a path of hardened child indexes
+    /// from the master key. See ExtendedSpendingKey for the derivation.
+    pub fn from_seed(
+        sapling: Arc<Sapling<J>>,
+        seed: &[u8],
+        path: &[u32],
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let master = ExtendedSpendingKey::master(sapling, seed)?;
+        Ok(master.derive_path(path)?.key().clone())
     }
 
     /// Generate a new random secret key.
@@ -201,6 +242,12 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
         self.incoming_viewing_key.generate_public_address()
     }
 
+    /// Generate the public address at the given diversifier index of this
+    /// key's incoming viewing key. See `IncomingViewKey::address_at`.
+    pub fn address_at(&self, index: u64) -> Result<PublicAddress<J>, errors::SaplingKeyError> {
+        self.incoming_viewing_key.address_at(index)
+    }
+
     // Write a bytes representation of this key to the provided stream
     pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
         let num_bytes_written = writer.write(&self.spending_key)?;
@@ -214,7 +261,9 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
         }
     }
 
-    /// Retrieve the private spending key
+    /// Retrieve the private spending key. The returned copy isn't zeroized
+    /// when the key is dropped, so callers that keep it around should zeroize
+    /// it themselves.
     pub fn spending_key(&self) -> [u8; 32] {
         self.spending_key
     }
@@ -225,6 +274,30 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
         bytes_to_hex(&self.spending_key)
     }
 
+    /// Private spending key as a Bech32m string for the given network, such as
+    /// `ironsk1...`.
+    pub fn to_bech32(&self, network: Network) -> String {
+        bech32::encode(
+            &network.bech32_prefix(SPENDING_KEY_KIND),
+            &self.spending_key,
+        )
+    }
+
+    /// Load a key from a Bech32m string for the given network.
+    pub fn from_bech32(
+        sapling: Arc<Sapling<J>>,
+        network: Network,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let bytes = bech32::decode(&network.bech32_prefix(SPENDING_KEY_KIND), value)?;
+        if bytes.len() != 32 {
+            return Err(errors::Bech32Error::InvalidLength.into());
+        }
+        let mut byte_arr = [0; 32];
+        byte_arr.clone_from_slice(&bytes[0..32]);
+        Self::new(sapling, byte_arr)
+    }
+
     /// Private spending key as words. This is even more human readable.
     ///
     /// We abuse the bip-39 to directly encode the key as words, instead of as
@@ -251,6 +324,17 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
         &self.incoming_viewing_key
     }
 
+    /// Retrieve the full viewing key, which can view all the transactions for
+    /// this key, including when notes are spent, but can't spend them.
+    pub fn full_viewing_key(&self) -> FullViewingKey<J> {
+        FullViewingKey {
+            sapling: self.sapling.clone(),
+            authorizing_key: self.authorizing_key.clone(),
+            nullifier_deriving_key: self.nullifier_deriving_key.clone(),
+            outgoing_viewing_key: self.outgoing_viewing_key.clone(),
+        }
+    }
+
     /// Retrieve both the view keys. These would normally used for third-party audits
     /// or for light clients.
     pub fn view_keys(&self) -> ViewKeys<J> {
@@ -286,15 +370,6 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> SaplingKey<J> {
             .expect("nullifier deriving key should be convertible to bytes")
     }
 
-    /// Adapter to convert this key to a viewing key for use in sapling
-    /// functions.
-    pub(crate) fn sapling_viewing_key(&self) -> ViewingKey<J> {
-        ViewingKey {
-            ak: self.authorizing_key.clone(),
-            nk: self.nullifier_deriving_key.clone(),
-        }
-    }
-
     /// Adapter to convert this key to a proof generation key for use in
     /// sapling functions
     pub(crate) fn sapling_proof_generation_key(&self) -> ProofGenerationKey<J> {
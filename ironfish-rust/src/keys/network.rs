@@ -0,0 +1,31 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+/// The network a key or address is used on. Bech32m strings start with a
+/// prefix for the network, so that a testnet address can't be pasted into a
+/// mainnet wallet by mistake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    /// Human-readable prefix for Bech32m strings of the given kind on this
+    /// network. Addresses use the bare network prefix, and keys append a
+    /// suffix identifying the kind of key, such as `ironivk`.
+    pub(crate) fn bech32_prefix(self, kind: &str) -> String {
+        let network = match self {
+            Network::Mainnet => "iron",
+            Network::Testnet => "tiron",
+        };
+        format!("{}{}", network, kind)
+    }
+}
+
+pub(crate) const ADDRESS_KIND: &str = "";
+pub(crate) const INCOMING_VIEW_KEY_KIND: &str = "ivk";
+pub(crate) const OUTGOING_VIEW_KEY_KIND: &str = "ovk";
+pub(crate) const FULL_VIEWING_KEY_KIND: &str = "fvk";
+pub(crate) const SPENDING_KEY_KIND: &str = "sk";
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use crate::bech32;
 use crate::serializing::{bytes_to_hex, hex_to_bytes, point_to_bytes};
 use rand::{thread_rng, Rng};
 use zcash_primitives::primitives::{Diversifier, PaymentAddress};
@@ -9,7 +10,7 @@ use zcash_primitives::primitives::{Diversifier, PaymentAddress};
 use std::{io, sync::Arc};
 use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder, ToUniform, Unknown};
 
-use super::{errors, IncomingViewKey, Sapling, SaplingKey};
+use super::{errors, IncomingViewKey, Network, Sapling, SaplingKey, ADDRESS_KIND};
 
 /// The address to which funds can be sent, stored as a diversifier and public
 /// transmission key. Combining a diversifier with an incoming_viewing_key allows
@@ -40,26 +41,32 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> PublicAddress<J> {
         sapling: Arc<Sapling<J>>,
         address_bytes: &[u8; 43],
     ) -> Result<PublicAddress<J>, errors::SaplingKeyError> {
-        let (diversifier, diversifier_point) =
-            PublicAddress::load_diversifier(&sapling.jubjub, &address_bytes[..11])?;
-        let transmission_key =
-            PublicAddress::load_transmission_key(&sapling.jubjub, &address_bytes[11..])?;
-
-        Ok(PublicAddress {
-            diversifier,
-            diversifier_point,
-            transmission_key,
-        })
+        Self::from_bytes(&sapling.jubjub, address_bytes)
     }
 
     /// Load a public address from a Read implementation (e.g: socket, file)
     pub fn read<R: io::Read>(
-        sapling: Arc<Sapling<J>>,
+        jubjub: &J::Params,
         reader: &mut R,
     ) -> Result<Self, errors::SaplingKeyError> {
         let mut address_bytes = [0; 43];
         reader.read_exact(&mut address_bytes)?;
-        Self::new(sapling, &address_bytes)
+        Self::from_bytes(jubjub, &address_bytes)
+    }
+
+    fn from_bytes(
+        jubjub: &J::Params,
+        address_bytes: &[u8; 43],
+    ) -> Result<PublicAddress<J>, errors::SaplingKeyError> {
+        let (diversifier, diversifier_point) =
+            PublicAddress::load_diversifier(jubjub, &address_bytes[..11])?;
+        let transmission_key = PublicAddress::load_transmission_key(jubjub, &address_bytes[11..])?;
+
+        Ok(PublicAddress {
+            diversifier,
+            diversifier_point,
+            transmission_key,
+        })
     }
 
     /// Initialize a public address from a sapling key and the bytes
@@ -126,6 +133,29 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> PublicAddress<J> {
         bytes_to_hex(&self.public_address())
     }
 
+    /// Retrieve the public address as a Bech32m string for the given network,
+    /// such as `iron1...`.
+    pub fn to_bech32(&self, network: Network) -> String {
+        bech32::encode(&network.bech32_prefix(ADDRESS_KIND), &self.public_address())
+    }
+
+    /// Convert a Bech32m string to a PublicAddress. Fails if the string is
+    /// for a different network, or if it is mistyped; the error gives the
+    /// position of the wrong character when it can be found.
+    pub fn from_bech32(
+        sapling: Arc<Sapling<J>>,
+        network: Network,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let bytes = bech32::decode(&network.bech32_prefix(ADDRESS_KIND), value)?;
+        if bytes.len() != 43 {
+            return Err(errors::SaplingKeyError::InvalidPublicAddress);
+        }
+        let mut byte_arr = [0; 43];
+        byte_arr.clone_from_slice(&bytes[0..43]);
+        Self::new(sapling, &byte_arr)
+    }
+
     /// Store the bytes of this public address in the given writer.
     pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_all(&self.public_address())?;
@@ -2,7 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use super::{shared_secret, PublicAddress, SaplingKey};
+use super::{
+    shared_secret, ExtendedSpendingKey, FullViewingKey, IncomingViewKey, Network, OutgoingViewKey,
+    PublicAddress, SaplingKey, HARDENED_KEY_START, KEYSTORE_SIZE,
+};
+use crate::bech32;
+use crate::errors::{Bech32Error, SaplingKeyError};
+use crate::note::{Memo, Note};
 use crate::sapling_bls12;
 use pairing::bls12_381::Bls12;
 
@@ -102,3 +108,303 @@ fn test_hex_conversion() {
 
     assert!(PublicAddress::from_hex(sapling.clone(), "invalid").is_err());
 }
+
+#[test]
+fn test_hd_derivation() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let seed = [7; 64];
+    let master: ExtendedSpendingKey<Bls12> =
+        ExtendedSpendingKey::master(sapling.clone(), &seed).expect("should derive master key");
+    let master2 = ExtendedSpendingKey::master(sapling.clone(), &seed).unwrap();
+    assert_eq!(master.key().spending_key, master2.key().spending_key);
+    assert_eq!(master.chain_code(), master2.chain_code());
+    assert_eq!(master.depth(), 0);
+
+    let child = master.derive_child(0).expect("should derive child");
+    let sibling = master.derive_child(1).expect("should derive child");
+    assert_ne!(child.key().spending_key, master.key().spending_key);
+    assert_ne!(child.key().spending_key, sibling.key().spending_key);
+    assert_ne!(child.chain_code(), master.chain_code());
+    assert_eq!(child.depth(), 1);
+    assert_eq!(child.child_index(), HARDENED_KEY_START);
+
+    let grandchild = child.derive_child(5).unwrap();
+    let from_path = master.derive_path(&[0, 5]).unwrap();
+    assert_eq!(grandchild.key().spending_key, from_path.key().spending_key);
+    let from_seed = SaplingKey::from_seed(sapling.clone(), &seed, &[0, 5]).unwrap();
+    assert_eq!(from_seed.spending_key, grandchild.key().spending_key);
+
+    let mut serialized = vec![];
+    grandchild.write(&mut serialized).unwrap();
+    assert_eq!(serialized.len(), 73);
+    let read_back = ExtendedSpendingKey::read(sapling.clone(), &serialized[..]).unwrap();
+    assert_eq!(read_back.depth(), 2);
+    assert_eq!(read_back.child_index(), grandchild.child_index());
+    assert_eq!(read_back.chain_code(), grandchild.chain_code());
+    assert_eq!(read_back.key().spending_key, grandchild.key().spending_key);
+    assert_eq!(
+        read_back.derive_child(3).unwrap().key().spending_key,
+        grandchild.derive_child(3).unwrap().key().spending_key
+    );
+
+    assert!(master.derive_child(HARDENED_KEY_START).is_err());
+    assert!(ExtendedSpendingKey::<Bls12>::master(sapling.clone(), &[0; 16]).is_err());
+}
+
+#[test]
+fn test_deterministic_addresses() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let view_key = key.incoming_view_key();
+
+    assert_eq!(view_key.diversifier_at(3), view_key.diversifier_at(3));
+    assert_ne!(view_key.diversifier_at(3), view_key.diversifier_at(4));
+
+    let (index, diversifier) = view_key
+        .next_valid_diversifier(0)
+        .expect("should find a valid diversifier");
+    assert_eq!(diversifier, view_key.diversifier_at(index));
+    for invalid_index in 0..index {
+        assert!(view_key.address_at(invalid_index).is_err());
+    }
+
+    let address = key.address_at(index).expect("should generate address");
+    let regenerated = view_key.address_at(index).unwrap();
+    assert_eq!(
+        address.hex_public_address(),
+        regenerated.hex_public_address()
+    );
+    assert_eq!(address.diversifier.0, diversifier);
+
+    let (next_index, _) = view_key.next_valid_diversifier(index + 1).unwrap();
+    assert!(next_index > index);
+    assert_ne!(
+        view_key
+            .address_at(next_index)
+            .unwrap()
+            .hex_public_address(),
+        address.hex_public_address()
+    );
+}
+
+#[test]
+fn test_bech32_encoding() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let address = key.generate_public_address();
+
+    let encoded_key = key.to_bech32(Network::Mainnet);
+    assert!(encoded_key.starts_with("ironsk1"));
+    let decoded_key =
+        SaplingKey::from_bech32(sapling.clone(), Network::Mainnet, &encoded_key).unwrap();
+    assert_eq!(decoded_key.spending_key, key.spending_key);
+
+    let encoded_ivk = key.incoming_view_key().to_bech32(Network::Testnet);
+    assert!(encoded_ivk.starts_with("tironivk1"));
+    let decoded_ivk =
+        IncomingViewKey::from_bech32(sapling.clone(), Network::Testnet, &encoded_ivk).unwrap();
+    assert_eq!(decoded_ivk.hex_key(), key.incoming_view_key().hex_key());
+
+    let encoded_ovk = key.outgoing_view_key().to_bech32(Network::Mainnet);
+    assert!(encoded_ovk.starts_with("ironovk1"));
+    let decoded_ovk =
+        OutgoingViewKey::from_bech32(sapling.clone(), Network::Mainnet, &encoded_ovk).unwrap();
+    assert_eq!(decoded_ovk.hex_key(), key.outgoing_view_key().hex_key());
+
+    let encoded_address = address.to_bech32(Network::Mainnet);
+    assert!(encoded_address.starts_with("iron1"));
+    let decoded_address =
+        PublicAddress::from_bech32(sapling.clone(), Network::Mainnet, &encoded_address).unwrap();
+    assert_eq!(
+        decoded_address.hex_public_address(),
+        address.hex_public_address()
+    );
+
+    // Keys and addresses for another network, or of another kind, are rejected
+    assert!(matches!(
+        PublicAddress::from_bech32(sapling.clone(), Network::Testnet, &encoded_address),
+        Err(SaplingKeyError::Bech32Error(Bech32Error::InvalidPrefix))
+    ));
+    assert!(matches!(
+        OutgoingViewKey::from_bech32(sapling.clone(), Network::Mainnet, &encoded_key),
+        Err(SaplingKeyError::Bech32Error(Bech32Error::InvalidPrefix))
+    ));
+
+    // A spending key payload of the wrong length is rejected as such
+    let short_key = bech32::encode("ironsk", &key.spending_key[..31]);
+    assert!(matches!(
+        SaplingKey::from_bech32(sapling.clone(), Network::Mainnet, &short_key),
+        Err(SaplingKeyError::Bech32Error(Bech32Error::InvalidLength))
+    ));
+
+    // A single mistyped character is located
+    let mut typo = encoded_address.into_bytes();
+    typo[10] = if typo[10] == b'x' { b'y' } else { b'x' };
+    let typo = String::from_utf8(typo).unwrap();
+    assert!(matches!(
+        PublicAddress::from_bech32(sapling.clone(), Network::Mainnet, &typo),
+        Err(SaplingKeyError::Bech32Error(Bech32Error::InvalidChecksum(
+            Some(10)
+        )))
+    ));
+
+    // Full viewing keys are longer than the checksum's guarantees hold for,
+    // so a typo is rejected but not located
+    let encoded_fvk = key.full_viewing_key().to_bech32(Network::Mainnet);
+    assert!(encoded_fvk.len() > 90);
+    let mut typo = encoded_fvk.into_bytes();
+    typo[10] = if typo[10] == b'x' { b'y' } else { b'x' };
+    let typo = String::from_utf8(typo).unwrap();
+    assert!(matches!(
+        FullViewingKey::from_bech32(sapling.clone(), Network::Mainnet, &typo),
+        Err(SaplingKeyError::Bech32Error(Bech32Error::InvalidChecksum(
+            None
+        )))
+    ));
+}
+
+#[test]
+fn test_full_viewing_key() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let view_key = key.full_viewing_key();
+
+    assert_eq!(
+        view_key.incoming_view_key().unwrap().hex_key(),
+        key.incoming_view_key().hex_key()
+    );
+    assert_eq!(
+        view_key.outgoing_view_key().hex_key(),
+        key.outgoing_view_key().hex_key()
+    );
+
+    let mut serialized = vec![];
+    view_key.write(&mut serialized).unwrap();
+    assert_eq!(serialized.len(), 96);
+    let read_back = FullViewingKey::read(sapling.clone(), &mut serialized[..].as_ref()).unwrap();
+    assert_eq!(read_back.hex_key(), view_key.hex_key());
+    let from_hex = FullViewingKey::from_hex(sapling.clone(), &view_key.hex_key()).unwrap();
+    assert_eq!(from_hex.authorizing_key(), view_key.authorizing_key());
+    let from_bech32 = FullViewingKey::from_bech32(
+        sapling.clone(),
+        Network::Mainnet,
+        &view_key.to_bech32(Network::Mainnet),
+    )
+    .unwrap();
+    assert_eq!(
+        from_bech32.nullifier_deriving_key(),
+        view_key.nullifier_deriving_key()
+    );
+
+    let note = Note::new(
+        sapling.clone(),
+        key.generate_public_address(),
+        42,
+        Memo::default(),
+    );
+    assert_eq!(
+        note.nullifier_from_view_key(&read_back, 7),
+        note.nullifier(&key, 7)
+    );
+    assert_ne!(
+        note.nullifier_from_view_key(&view_key, 7),
+        note.nullifier_from_view_key(&view_key, 8)
+    );
+}
+
+#[test]
+fn test_keystore() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+
+    // A low scrypt cost keeps the test fast; the format is the same.
+    let keystore = key.to_keystore_with_cost("correct horse", 4, 8, 1);
+    assert_eq!(keystore.len(), KEYSTORE_SIZE);
+    assert_ne!(
+        key.to_keystore_with_cost("correct horse", 4, 8, 1),
+        keystore,
+        "each keystore should have its own salt"
+    );
+
+    let loaded = SaplingKey::from_keystore(sapling.clone(), "correct horse", &keystore).unwrap();
+    assert_eq!(loaded.spending_key, key.spending_key);
+
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_keystore(sapling.clone(), "wrong horse", &keystore),
+        Err(SaplingKeyError::IncorrectPassword)
+    ));
+
+    let mut tampered = keystore.clone();
+    tampered[20] ^= 1;
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_keystore(sapling.clone(), "correct horse", &tampered),
+        Err(SaplingKeyError::IncorrectPassword)
+    ));
+
+    // Just above the cap for each of log_n, r and p
+    for (offset, value) in &[(1, 19), (2, 9), (6, 5)] {
+        let mut expensive = keystore.clone();
+        expensive[*offset] = *value;
+        assert!(matches!(
+            SaplingKey::<Bls12>::from_keystore(sapling.clone(), "correct horse", &expensive),
+            Err(SaplingKeyError::InvalidKeystore)
+        ));
+    }
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_keystore(sapling.clone(), "correct horse", &keystore[1..]),
+        Err(SaplingKeyError::InvalidKeystore)
+    ));
+}
+
+#[test]
+fn test_debug_redacts_secrets() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let debug = format!("{:?}", key);
+    assert!(debug.contains("<redacted>"));
+    assert!(!debug.contains(&key.hex_spending_key()));
+}
+
+#[test]
+fn test_mnemonic_phrases() {
+    let sapling = &*sapling_bls12::SAPLING;
+
+    let phrase = SaplingKey::<Bls12>::generate_mnemonic("en").unwrap();
+    assert_eq!(phrase.split_whitespace().count(), 24);
+
+    let key: SaplingKey<Bls12> =
+        SaplingKey::from_mnemonic(sapling.clone(), "en", &phrase, None, &[0]).unwrap();
+    let same_key: SaplingKey<Bls12> =
+        SaplingKey::from_mnemonic(sapling.clone(), "en", &phrase, Some(""), &[0]).unwrap();
+    assert_eq!(key.spending_key, same_key.spending_key);
+    let with_passphrase: SaplingKey<Bls12> =
+        SaplingKey::from_mnemonic(sapling.clone(), "en", &phrase, Some("hunter2"), &[0]).unwrap();
+    assert_ne!(key.spending_key, with_passphrase.spending_key);
+
+    // 12 word phrases can be used as seeds, but can't hold a key directly
+    let twelve_words = "abandon abandon abandon abandon abandon abandon \
+                        abandon abandon abandon abandon abandon about";
+    assert!(
+        SaplingKey::<Bls12>::from_mnemonic(sapling.clone(), "en", twelve_words, None, &[]).is_ok()
+    );
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_words(sapling.clone(), "en", twelve_words.to_string()),
+        Err(SaplingKeyError::InvalidMnemonicLength)
+    ));
+
+    let bad_checksum = twelve_words.replace("about", "abandon");
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_mnemonic(sapling.clone(), "en", &bad_checksum, None, &[]),
+        Err(SaplingKeyError::InvalidMnemonicChecksum)
+    ));
+    let unknown_word = twelve_words.replace("about", "aboot");
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_mnemonic(sapling.clone(), "en", &unknown_word, None, &[]),
+        Err(SaplingKeyError::InvalidWord)
+    ));
+    let eleven_words = twelve_words.replacen("abandon ", "", 1);
+    assert!(matches!(
+        SaplingKey::<Bls12>::from_mnemonic(sapling.clone(), "en", &eleven_words, None, &[]),
+        Err(SaplingKeyError::InvalidMnemonicLength)
+    ));
+}
@@ -12,7 +12,11 @@
 //! that you have spent.
 //!
 
-use super::{errors, PublicAddress, Sapling};
+use super::{
+    errors, mnemonic::words_to_bytes, Network, PublicAddress, Sapling, SaplingKey,
+    FULL_VIEWING_KEY_KIND, INCOMING_VIEW_KEY_KIND, OUTGOING_VIEW_KEY_KIND,
+};
+use crate::bech32;
 use crate::serializing::{
     bytes_to_hex, hex_to_bytes, point_to_bytes, read_scalar, scalar_to_bytes,
 };
@@ -21,9 +25,11 @@ use blake2b_simd::Params as Blake2b;
 use rand::{thread_rng, Rng};
 
 use std::{io, sync::Arc};
-use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder};
+use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder, Unknown};
+use zcash_primitives::primitives::ViewingKey;
 
 const DIFFIE_HELLMAN_PERSONALIZATION: &[u8; 16] = b"Beanstalk shared";
+const DIVERSIFIER_PERSONALIZATION: &[u8; 16] = b"IronFishDiversfy";
 
 /// Key that allows someone to view a transaction that you have received.
 ///
@@ -69,14 +75,21 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> IncomingViewKey<J> {
         language_code: &str,
         value: String,
     ) -> Result<Self, errors::SaplingKeyError> {
-        let language = Language::from_language_code(language_code)
-            .ok_or(errors::SaplingKeyError::InvalidLanguageEncoding)?;
-        let mnemonic = Mnemonic::from_phrase(&value, language)
-            .map_err(|_| errors::SaplingKeyError::InvalidPaymentAddress)?;
-        let bytes = mnemonic.entropy();
-        let mut byte_arr = [0; 32];
-        byte_arr.clone_from_slice(&bytes[0..32]);
-        Self::read(sapling, &mut byte_arr[..].as_ref())
+        let bytes = words_to_bytes(language_code, &value)?;
+        Self::read(sapling, &mut bytes[..].as_ref())
+    }
+
+    /// Load a key from a Bech32m string for the given network.
+    pub fn from_bech32(
+        sapling: Arc<Sapling<J>>,
+        network: Network,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let bytes = bech32::decode(&network.bech32_prefix(INCOMING_VIEW_KEY_KIND), value)?;
+        if bytes.len() != 32 {
+            return Err(errors::SaplingKeyError::InvalidViewingKey);
+        }
+        Self::read(sapling, &mut bytes[..].as_ref())
     }
 
     /// Viewing key as hexadecimal, for readability.
@@ -84,6 +97,15 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> IncomingViewKey<J> {
         bytes_to_hex(&scalar_to_bytes(&self.view_key))
     }
 
+    /// Viewing key as a Bech32m string for the given network, such as
+    /// `ironivk1...`.
+    pub fn to_bech32(&self, network: Network) -> String {
+        bech32::encode(
+            &network.bech32_prefix(INCOMING_VIEW_KEY_KIND),
+            &scalar_to_bytes(&self.view_key),
+        )
+    }
+
     /// Even more readable
     pub fn words_key(&self, language_code: &str) -> Result<String, errors::SaplingKeyError> {
         let language = Language::from_language_code(language_code)
@@ -125,6 +147,55 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> IncomingViewKey<J> {
         public_address
     }
 
+    /// Calculate the diversifier at the given index.
+    ///
+    /// Diversifiers are derived by hashing the index with this key, so the
+    /// same index always gives the same diversifier and a wallet can
+    /// regenerate every address it handed out from the key and the indexes
+    /// alone. Without the key, diversifiers at different indexes can't be
+    /// linked to each other.
+    ///
+    /// Roughly half of all diversifiers are not valid for creating an
+    /// address; see `next_valid_diversifier`.
+    pub fn diversifier_at(&self, index: u64) -> [u8; 11] {
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .key(&scalar_to_bytes(&self.view_key))
+            .personal(DIVERSIFIER_PERSONALIZATION)
+            .to_state();
+        hasher.update(&index.to_le_bytes());
+
+        let mut diversifier = [0; 11];
+        diversifier.copy_from_slice(&hasher.finalize().as_bytes()[..11]);
+        diversifier
+    }
+
+    /// Find the first index at or after `start` whose diversifier is valid,
+    /// returning the index and the diversifier.
+    pub fn next_valid_diversifier(
+        &self,
+        start: u64,
+    ) -> Result<(u64, [u8; 11]), errors::SaplingKeyError> {
+        let mut index = start;
+        loop {
+            let diversifier = self.diversifier_at(index);
+            if self.public_address(&diversifier).is_ok() {
+                return Ok((index, diversifier));
+            }
+            index = index
+                .checked_add(1)
+                .ok_or(errors::SaplingKeyError::DiversificationError)?;
+        }
+    }
+
+    /// Generate the public address at the given diversifier index.
+    ///
+    /// Fails if the diversifier at that index is not valid. Use
+    /// `next_valid_diversifier` to find the next index that has an address.
+    pub fn address_at(&self, index: u64) -> Result<PublicAddress<J>, errors::SaplingKeyError> {
+        self.public_address(&self.diversifier_at(index))
+    }
+
     /// Calculate the shared secret key given the ephemeral public key that was
     /// created for a transaction.
     pub(crate) fn shared_secret(
@@ -177,11 +248,20 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> OutgoingViewKey<J> {
         language_code: &str,
         value: String,
     ) -> Result<Self, errors::SaplingKeyError> {
-        let language = Language::from_language_code(language_code)
-            .ok_or(errors::SaplingKeyError::InvalidLanguageEncoding)?;
-        let mnemonic = Mnemonic::from_phrase(&value, language)
-            .map_err(|_| errors::SaplingKeyError::InvalidPaymentAddress)?;
-        let bytes = mnemonic.entropy();
+        let view_key = words_to_bytes(language_code, &value)?;
+        Ok(Self { sapling, view_key })
+    }
+
+    /// Load a key from a Bech32m string for the given network.
+    pub fn from_bech32(
+        sapling: Arc<Sapling<J>>,
+        network: Network,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let bytes = bech32::decode(&network.bech32_prefix(OUTGOING_VIEW_KEY_KIND), value)?;
+        if bytes.len() != 32 {
+            return Err(errors::SaplingKeyError::InvalidViewingKey);
+        }
         let mut view_key = [0; 32];
         view_key.clone_from_slice(&bytes[0..32]);
         Ok(Self { sapling, view_key })
@@ -192,6 +272,15 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> OutgoingViewKey<J> {
         bytes_to_hex(&self.view_key)
     }
 
+    /// Viewing key as a Bech32m string for the given network, such as
+    /// `ironovk1...`.
+    pub fn to_bech32(&self, network: Network) -> String {
+        bech32::encode(
+            &network.bech32_prefix(OUTGOING_VIEW_KEY_KIND),
+            &self.view_key,
+        )
+    }
+
     /// Even more readable
     pub fn words_key(&self, language_code: &str) -> Result<String, errors::SaplingKeyError> {
         let language = Language::from_language_code(language_code)
@@ -201,6 +290,163 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> OutgoingViewKey<J> {
     }
 }
 
+/// Key that allows someone to view all the transactions for an account,
+/// including when its notes are spent.
+///
+/// The incoming and outgoing view keys show which notes were received and
+/// sent, but seeing when a received note is spent requires its nullifier,
+/// which is derived from the authorizing key (`ak`) and nullifier deriving
+/// key (`nk`). The full viewing key holds those along with the outgoing view
+/// key, and the incoming view key can be derived from it. It can't be used
+/// to spend notes.
+///
+/// Referred to as `fvk` in the literature.
+#[derive(Clone)]
+pub struct FullViewingKey<J: JubjubEngine + pairing::MultiMillerLoop> {
+    pub(crate) sapling: Arc<Sapling<J>>,
+
+    /// Referred to as `ak` in the literature.
+    pub(crate) authorizing_key: edwards::Point<J, PrimeOrder>,
+
+    /// Referred to as `nk` in the literature.
+    pub(crate) nullifier_deriving_key: edwards::Point<J, PrimeOrder>,
+
+    pub(crate) outgoing_viewing_key: OutgoingViewKey<J>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> FullViewingKey<J> {
+    /// Load a key from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(
+        sapling: Arc<Sapling<J>>,
+        reader: &mut R,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let authorizing_key = Self::read_point(&sapling.jubjub, reader)?;
+        let nullifier_deriving_key = Self::read_point(&sapling.jubjub, reader)?;
+        let mut view_key = [0; 32];
+        reader.read_exact(&mut view_key)?;
+        Ok(FullViewingKey {
+            sapling: sapling.clone(),
+            authorizing_key,
+            nullifier_deriving_key,
+            outgoing_viewing_key: OutgoingViewKey { sapling, view_key },
+        })
+    }
+
+    /// Load a key from a string of hexadecimal digits
+    pub fn from_hex(
+        sapling: Arc<Sapling<J>>,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        match hex_to_bytes(&value) {
+            Err(()) => Err(errors::SaplingKeyError::InvalidViewingKey),
+            Ok(bytes) => {
+                if bytes.len() != 96 {
+                    Err(errors::SaplingKeyError::InvalidViewingKey)
+                } else {
+                    Self::read(sapling, &mut bytes[..].as_ref())
+                }
+            }
+        }
+    }
+
+    /// Load a key from a Bech32m string for the given network.
+    pub fn from_bech32(
+        sapling: Arc<Sapling<J>>,
+        network: Network,
+        value: &str,
+    ) -> Result<Self, errors::SaplingKeyError> {
+        let bytes = bech32::decode(&network.bech32_prefix(FULL_VIEWING_KEY_KIND), value)?;
+        if bytes.len() != 96 {
+            return Err(errors::SaplingKeyError::InvalidViewingKey);
+        }
+        Self::read(sapling, &mut bytes[..].as_ref())
+    }
+
+    /// Store the bytes of this key in the given writer: the authorizing key,
+    /// nullifier deriving key and outgoing view key, 96 bytes in total.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        self.authorizing_key.write(&mut writer)?;
+        self.nullifier_deriving_key.write(&mut writer)?;
+        writer.write_all(&self.outgoing_viewing_key.view_key)
+    }
+
+    /// Viewing key as hexadecimal, for readability.
+    pub fn hex_key(&self) -> String {
+        bytes_to_hex(&self.key_bytes())
+    }
+
+    /// Viewing key as a Bech32m string for the given network, such as
+    /// `ironfvk1...`.
+    pub fn to_bech32(&self, network: Network) -> String {
+        bech32::encode(
+            &network.bech32_prefix(FULL_VIEWING_KEY_KIND),
+            &self.key_bytes(),
+        )
+    }
+
+    /// Retrieve the byte representation of the authorizing key
+    pub fn authorizing_key(&self) -> [u8; 32] {
+        point_to_bytes(&self.authorizing_key)
+            .expect("authorizing key should be convertible to bytes")
+    }
+
+    /// Retrieve the byte representation of the nullifier_deriving_key
+    pub fn nullifier_deriving_key(&self) -> [u8; 32] {
+        point_to_bytes(&self.nullifier_deriving_key)
+            .expect("nullifier deriving key should be convertible to bytes")
+    }
+
+    /// Retrieve the outgoing viewing key
+    pub fn outgoing_view_key(&self) -> &OutgoingViewKey<J> {
+        &self.outgoing_viewing_key
+    }
+
+    /// Derive the incoming viewing key from the authorizing key and
+    /// nullifier deriving key.
+    pub fn incoming_view_key(&self) -> Result<IncomingViewKey<J>, errors::SaplingKeyError> {
+        Ok(IncomingViewKey {
+            sapling: self.sapling.clone(),
+            view_key: SaplingKey::hash_viewing_key(
+                &self.authorizing_key,
+                &self.nullifier_deriving_key,
+            )?,
+        })
+    }
+
+    /// Retrieve both the incoming and outgoing view keys.
+    pub fn view_keys(&self) -> Result<ViewKeys<J>, errors::SaplingKeyError> {
+        Ok(ViewKeys {
+            incoming: self.incoming_view_key()?,
+            outgoing: self.outgoing_viewing_key.clone(),
+        })
+    }
+
+    /// Adapter to convert this key to a viewing key for use in sapling
+    /// functions.
+    pub(crate) fn sapling_viewing_key(&self) -> ViewingKey<J> {
+        ViewingKey {
+            ak: self.authorizing_key.clone(),
+            nk: self.nullifier_deriving_key.clone(),
+        }
+    }
+
+    fn key_bytes(&self) -> [u8; 96] {
+        let mut result = [0; 96];
+        self.write(&mut result[..])
+            .expect("should be able to serialize full viewing key");
+        result
+    }
+
+    fn read_point<R: io::Read>(
+        jubjub: &J::Params,
+        reader: &mut R,
+    ) -> Result<edwards::Point<J, PrimeOrder>, errors::SaplingKeyError> {
+        edwards::Point::<J, Unknown>::read(reader, jubjub)?
+            .as_prime_order(jubjub)
+            .ok_or(errors::SaplingKeyError::InvalidViewingKey)
+    }
+}
+
 /// Pair of outgoing and incoming view keys for a complete audit
 /// of spends and receipts
 #[derive(Clone)]
@@ -9,8 +9,17 @@ extern crate lazy_static;
 extern crate shrinkwraprs;
 
 use bellman::groth16;
+use serializing::read_to_end_hashed;
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    ops::Deref,
+    path::Path,
+    sync::Arc,
+};
 use zcash_primitives::jubjub::{edwards, JubjubEngine};
 
+mod bech32;
 mod serializing;
 
 pub mod errors;
@@ -18,22 +27,35 @@ pub mod keys;
 pub mod merkle_note;
 pub mod merkle_note_hash;
 pub mod note;
+pub mod note_commitment_tree;
+pub mod note_scanner;
 pub mod nullifiers;
 pub mod receiving;
 pub mod spending;
 pub mod transaction;
 pub mod witness;
 pub use {
-    keys::{IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, ViewKeys},
-    merkle_note::MerkleNote,
+    keys::{FullViewingKey, IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, ViewKeys},
+    merkle_note::{CompactMerkleNote, MerkleNote, SentNoteInfo},
     merkle_note_hash::MerkleNoteHash,
     note::Note,
+    note_commitment_tree::NoteCommitmentTree,
+    note_scanner::{NoteScanner, ScannedNote},
     receiving::{ReceiptParams, ReceiptProof},
     spending::{SpendParams, SpendProof},
-    transaction::{ProposedTransaction, SimpleTransaction, Transaction},
+    transaction::{
+        BatchVerifier, PaymentDisclosure, ProposedTransaction, SimpleTransaction, Transaction,
+    },
 };
 pub mod sapling_bls12;
 
+// TODO: We'll need to build our own parameters using a trusted set up at some point.
+// These params were borrowed from zcash
+#[cfg(feature = "embedded-params")]
+static SPEND_PARAMS_BYTES: &[u8] = include_bytes!("sapling_params/sapling-spend.params");
+#[cfg(feature = "embedded-params")]
+static RECEIPT_PARAMS_BYTES: &[u8] = include_bytes!("sapling_params/sapling-output.params");
+
 #[cfg(test)]
 pub(crate) mod test_util; // I'm not sure if this is the right way to publish the utility library.
 
@@ -45,42 +67,95 @@ compile_error!("feature \"native\" and feature \"wasm\" cannot be enabled at the
 //
 // spend and output are two arithmetic circuits for use in zksnark calculations provided by Bellman.
 // Though the *_params have a verifying key on them, they are not the prepared verifying keys,
-// so we store the prepared keys separately at the time of loading the params.
+// so we store the prepared keys separately at the time of loading the params, in a
+// SaplingVerifier. Sapling dereferences to that verifier, so it can be used anywhere that only
+// needs to verify.
 //
 // The values are all loaded from a file in serialized form.
 pub struct Sapling<J: JubjubEngine + pairing::MultiMillerLoop> {
     spend_params: groth16::Parameters<J>,
     receipt_params: groth16::Parameters<J>,
-    spend_verifying_key: groth16::PreparedVerifyingKey<J>,
-    receipt_verifying_key: groth16::PreparedVerifyingKey<J>,
-    pub jubjub: J::Params, // Initial point on the jubjub curve
+    verifier: Arc<SaplingVerifier<J>>,
 }
 
+/// BLAKE2b-512 digest of the spend circuit parameters, as published by zcash.
+const SPEND_PARAMS_HASH: &str = "8270785a1a0d0bc77196f000ee6d221c9c9894f55307bd9357c3f0105d31ca63991ab91324160d8f53e2bbd3c2633a6eb8bdf5205d822e7f3f73edac51b2b70c";
+
+/// BLAKE2b-512 digest of the output circuit parameters in src/sapling_params.
+const RECEIPT_PARAMS_HASH: &str = "483a56b96f5d33d8d9ee82f8c7b2c32bf99c2ea2e465d239eb7399cf68abb01a1ee179c7911bea56deb3ed8b737f56262a852572810a9611558711ce4fed0b37";
+
 impl<J: JubjubEngine + pairing::MultiMillerLoop> Sapling<J> {
-    /// Initialize a Sapling instance and prepare for proving. Load the parameters from a config file
-    /// at a known location (`./sapling_params`, for now).
+    /// Initialize a Sapling instance and prepare for proving, using the parameters that were
+    /// embedded in the binary at compile time.
     ///
     /// The argument `jubjub` is the parameters for a given JubjubEngine. They have to be passed
     /// in instead of being constructed locally because this code is generic across curves, but
     /// zcash_primitives's `J::Params` trait doesn't have a method to construct a default.
+    ///
+    /// The parameters add several megabytes to the binary, so this is only available with the
+    /// `embedded-params` feature. Use `from_paths` or `from_readers` to load them at runtime
+    /// instead.
+    #[cfg(feature = "embedded-params")]
     pub fn load(jubjub: J::Params) -> Self {
-        // TODO: We'll need to build our own parameters using a trusted set up at some point.
-        // These params were borrowed from zcash
-        let spend_bytes = include_bytes!("sapling_params/sapling-spend.params");
-        let receipt_bytes = include_bytes!("sapling_params/sapling-output.params");
+        let spend_params = Sapling::load_params(SPEND_PARAMS_BYTES);
+        let receipt_params = Sapling::load_params(RECEIPT_PARAMS_BYTES);
+
+        Sapling::new(jubjub, spend_params, receipt_params)
+    }
+
+    /// Initialize a Sapling instance from the parameter files at the given paths.
+    ///
+    /// Fails if either file can't be read or parsed, or doesn't match the published digest
+    /// for its circuit.
+    pub fn from_paths<P: AsRef<Path>, Q: AsRef<Path>>(
+        jubjub: J::Params,
+        spend_path: P,
+        receipt_path: Q,
+    ) -> Result<Self, errors::SaplingParamsError> {
+        let spend_file = BufReader::new(File::open(spend_path)?);
+        let receipt_file = BufReader::new(File::open(receipt_path)?);
+        Sapling::from_readers(jubjub, spend_file, receipt_file)
+    }
+
+    /// Initialize a Sapling instance from the serialized spend and output parameters.
+    ///
+    /// Everything is read from each reader and has to match the published digest for its
+    /// circuit before it is parsed, because the parameters are parsed without checking
+    /// that their points are valid.
+    pub fn from_readers<R: io::Read, S: io::Read>(
+        jubjub: J::Params,
+        spend_reader: R,
+        receipt_reader: S,
+    ) -> Result<Self, errors::SaplingParamsError> {
+        let (spend_bytes, receipt_bytes) = read_params(spend_reader, receipt_reader)?;
+        let spend_params = groth16::Parameters::read(&spend_bytes[..], false)?;
+        let receipt_params = groth16::Parameters::read(&receipt_bytes[..], false)?;
+
+        Ok(Sapling::new(jubjub, spend_params, receipt_params))
+    }
 
-        let spend_params = Sapling::load_params(&spend_bytes[..]);
-        let receipt_params = Sapling::load_params(&receipt_bytes[..]);
+    /// Get the verifier holding the verifying keys for these parameters. It can be cloned
+    /// to read and verify transactions without keeping the proving parameters around.
+    pub fn verifier(&self) -> &Arc<SaplingVerifier<J>> {
+        &self.verifier
+    }
 
-        let spend_vk = groth16::prepare_verifying_key(&spend_params.vk);
-        let receipt_vk = groth16::prepare_verifying_key(&receipt_params.vk);
+    /// Prepare the verifying keys for the given parameters.
+    fn new(
+        jubjub: J::Params,
+        spend_params: groth16::Parameters<J>,
+        receipt_params: groth16::Parameters<J>,
+    ) -> Self {
+        let verifier = Arc::new(SaplingVerifier::new(
+            jubjub,
+            spend_params.vk.clone(),
+            receipt_params.vk.clone(),
+        ));
 
         Sapling {
-            spend_verifying_key: spend_vk,
-            receipt_verifying_key: receipt_vk,
             spend_params,
             receipt_params,
-            jubjub,
+            verifier,
         }
     }
 
@@ -89,11 +164,111 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Sapling<J> {
     /// curve.
     ///
     /// NOTE: If this is stupidly slow for you, try compiling in --release mode
+    #[cfg(feature = "embedded-params")]
     fn load_params(bytes: &[u8]) -> groth16::Parameters<J> {
-        groth16::Parameters::read(bytes, false).unwrap()
+        groth16::Parameters::read(bytes, false).expect("embedded parameters should be valid")
     }
 }
 
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Deref for Sapling<J> {
+    type Target = SaplingVerifier<J>;
+
+    fn deref(&self) -> &SaplingVerifier<J> {
+        &self.verifier
+    }
+}
+
+// The part of the sapling API needed to verify transactions, without the proving parameters.
+//
+// The proving parameters are by far the largest part of Sapling, and nodes that only validate
+// blocks never create proofs. A SaplingVerifier only holds the verifying keys for the spend and
+// output circuits, which are read from the start of the same parameter files.
+pub struct SaplingVerifier<J: JubjubEngine + pairing::MultiMillerLoop> {
+    spend_vk: groth16::VerifyingKey<J>,
+    receipt_vk: groth16::VerifyingKey<J>,
+    spend_verifying_key: groth16::PreparedVerifyingKey<J>,
+    receipt_verifying_key: groth16::PreparedVerifyingKey<J>,
+    pub jubjub: J::Params, // Initial point on the jubjub curve
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> SaplingVerifier<J> {
+    /// Construct a verifier from the verifying keys of the spend and output circuits.
+    pub fn new(
+        jubjub: J::Params,
+        spend_vk: groth16::VerifyingKey<J>,
+        receipt_vk: groth16::VerifyingKey<J>,
+    ) -> Self {
+        SaplingVerifier {
+            spend_verifying_key: groth16::prepare_verifying_key(&spend_vk),
+            receipt_verifying_key: groth16::prepare_verifying_key(&receipt_vk),
+            spend_vk,
+            receipt_vk,
+            jubjub,
+        }
+    }
+
+    /// Construct a verifier from the parameters that were embedded in the binary at compile
+    /// time. Only the verifying keys are parsed.
+    #[cfg(feature = "embedded-params")]
+    pub fn load(jubjub: J::Params) -> Self {
+        let spend_vk = groth16::VerifyingKey::read(SPEND_PARAMS_BYTES)
+            .expect("embedded parameters should be valid");
+        let receipt_vk = groth16::VerifyingKey::read(RECEIPT_PARAMS_BYTES)
+            .expect("embedded parameters should be valid");
+
+        SaplingVerifier::new(jubjub, spend_vk, receipt_vk)
+    }
+
+    /// Construct a verifier from the parameter files at the given paths.
+    ///
+    /// Fails if either file can't be read or parsed, or doesn't match the published digest
+    /// for its circuit.
+    pub fn from_paths<P: AsRef<Path>, Q: AsRef<Path>>(
+        jubjub: J::Params,
+        spend_path: P,
+        receipt_path: Q,
+    ) -> Result<Self, errors::SaplingParamsError> {
+        let spend_file = BufReader::new(File::open(spend_path)?);
+        let receipt_file = BufReader::new(File::open(receipt_path)?);
+        SaplingVerifier::from_readers(jubjub, spend_file, receipt_file)
+    }
+
+    /// Construct a verifier from the serialized spend and output parameters.
+    ///
+    /// Only the verifying keys at the start of each are kept. The rest is read to check the
+    /// digest, but isn't parsed.
+    pub fn from_readers<R: io::Read, S: io::Read>(
+        jubjub: J::Params,
+        spend_reader: R,
+        receipt_reader: S,
+    ) -> Result<Self, errors::SaplingParamsError> {
+        let (spend_bytes, receipt_bytes) = read_params(spend_reader, receipt_reader)?;
+        let spend_vk = groth16::VerifyingKey::read(&spend_bytes[..])?;
+        let receipt_vk = groth16::VerifyingKey::read(&receipt_bytes[..])?;
+
+        Ok(SaplingVerifier::new(jubjub, spend_vk, receipt_vk))
+    }
+}
+
+// Read the serialized spend and output parameters, and check them against the published
+// digests before anything is parsed from them.
+fn read_params<R: io::Read, S: io::Read>(
+    spend_reader: R,
+    receipt_reader: S,
+) -> Result<(Vec<u8>, Vec<u8>), errors::SaplingParamsError> {
+    let (spend_bytes, spend_hash) = read_to_end_hashed(spend_reader)?;
+    if spend_hash != SPEND_PARAMS_HASH {
+        return Err(errors::SaplingParamsError::SpendParamsHashMismatch);
+    }
+
+    let (receipt_bytes, receipt_hash) = read_to_end_hashed(receipt_reader)?;
+    if receipt_hash != RECEIPT_PARAMS_HASH {
+        return Err(errors::SaplingParamsError::ReceiptParamsHashMismatch);
+    }
+
+    Ok((spend_bytes, receipt_bytes))
+}
+
 // TODO: This belongs in a utility library if we ever need one
 fn is_small_order<J: JubjubEngine + pairing::MultiMillerLoop, Order>(
     jubjub: &J::Params,
@@ -101,3 +276,68 @@ fn is_small_order<J: JubjubEngine + pairing::MultiMillerLoop, Order>(
 ) -> bool {
     point.double(jubjub).double(jubjub).double(jubjub) == edwards::Point::zero()
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        errors::SaplingParamsError,
+        sapling_bls12::{Sapling, SaplingVerifier},
+    };
+    use std::{fs::File, io::BufReader};
+    use zcash_primitives::jubjub::JubjubBls12;
+
+    const SPEND_PARAMS_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/sapling_params/sapling-spend.params"
+    );
+    const RECEIPT_PARAMS_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/sapling_params/sapling-output.params"
+    );
+
+    #[test]
+    fn test_load_params_from_paths() {
+        let sapling =
+            Sapling::from_paths(JubjubBls12::new(), SPEND_PARAMS_PATH, RECEIPT_PARAMS_PATH)
+                .expect("should load the shipped params");
+        let verifier =
+            SaplingVerifier::from_paths(JubjubBls12::new(), SPEND_PARAMS_PATH, RECEIPT_PARAMS_PATH)
+                .expect("should load the shipped params");
+        assert!(sapling.verifier().spend_vk == verifier.spend_vk);
+        assert!(sapling.verifier().receipt_vk == verifier.receipt_vk);
+
+        let spend_file = BufReader::new(File::open(SPEND_PARAMS_PATH).unwrap());
+        let receipt_file = BufReader::new(File::open(RECEIPT_PARAMS_PATH).unwrap());
+        assert!(Sapling::from_readers(JubjubBls12::new(), spend_file, receipt_file).is_ok());
+    }
+
+    #[test]
+    fn test_from_readers_checks_params() {
+        let receipt_bytes = include_bytes!("sapling_params/sapling-output.params");
+
+        match Sapling::from_readers(JubjubBls12::new(), &[0u8; 64][..], &receipt_bytes[..]) {
+            Err(SaplingParamsError::SpendParamsHashMismatch) => (),
+            _ => panic!("should not be able to parse invalid params"),
+        }
+
+        match Sapling::from_readers(JubjubBls12::new(), &receipt_bytes[..], &receipt_bytes[..]) {
+            Err(SaplingParamsError::SpendParamsHashMismatch) => (),
+            _ => panic!("should not accept output params as spend params"),
+        }
+
+        match SaplingVerifier::from_readers(
+            JubjubBls12::new(),
+            &receipt_bytes[..],
+            &receipt_bytes[..],
+        ) {
+            Err(SaplingParamsError::SpendParamsHashMismatch) => (),
+            _ => panic!("should not accept output params as spend params"),
+        }
+
+        let spend_file = BufReader::new(File::open(SPEND_PARAMS_PATH).unwrap());
+        match SaplingVerifier::from_readers(JubjubBls12::new(), spend_file, &[0u8; 64][..]) {
+            Err(SaplingParamsError::ReceiptParamsHashMismatch) => (),
+            _ => panic!("should not accept invalid output params"),
+        }
+    }
+}
@@ -6,19 +6,21 @@
 /// A tree containing these values can serve as a snapshot of the entire chain.
 use super::{
     errors,
-    keys::{shared_secret, IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey},
-    note::{Note, ENCRYPTED_NOTE_SIZE},
-    serializing::{aead, read_scalar},
+    keys::{shared_secret, IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, ViewKeys},
+    note::{Memo, Note, NoteVersion},
+    serializing::{aead, read_scalar, zeroize_scalar},
     witness::{WitnessNode, WitnessTrait},
-    MerkleNoteHash, Sapling,
+    MerkleNoteHash,
 };
 
 use blake2b_simd::Params as Blake2b;
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use ff::PrimeField;
 use zcash_primitives::primitives::ValueCommitment;
 
-use std::{convert::TryInto, io, sync::Arc};
+use std::{convert::TryInto, io};
 use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder, Unknown};
+use zeroize::Zeroize;
 
 pub const ENCRYPTED_SHARED_KEY_SIZE: usize = 64;
 /// The note encryption keys are used to allow the spender to
@@ -33,6 +35,13 @@ pub const NOTE_ENCRYPTION_MINER_KEYS: &[u8; ENCRYPTED_SHARED_KEY_SIZE + aead::MA
     b"Beanstalk note encryption miner key000000000000000000000000000000000000000000000";
 const SHARED_KEY_PERSONALIZATION: &[u8; 16] = b"Beanstalk Keyenc";
 
+/// Set in the last byte of the serialized note commitment when the encrypted
+/// note uses a versioned plaintext, in which case the version byte follows
+/// the commitment. Note commitments are always less than 2^255, so the bit is
+/// never set in notes serialized before versioned plaintexts existed, which
+/// keep their original layout.
+const VERSIONED_NOTE_FLAG: u8 = 0x80;
+
 #[derive(Clone)]
 pub struct MerkleNote<J: JubjubEngine + pairing::MultiMillerLoop> {
     /// Randomized value commitment. Sometimes referred to as
@@ -48,8 +57,13 @@ pub struct MerkleNote<J: JubjubEngine + pairing::MultiMillerLoop> {
     /// keys::shared_secret to understand how this is used
     pub(crate) ephemeral_public_key: edwards::Point<J, PrimeOrder>,
 
-    /// note as encrypted by the diffie hellman public key
-    pub(crate) encrypted_note: [u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+    /// Version of the note plaintext, which determines the size of the
+    /// encrypted note
+    version: NoteVersion,
+
+    /// note as encrypted by the diffie hellman public key. Always
+    /// `version.encrypted_size()` bytes long.
+    encrypted_note: Vec<u8>,
 
     /// Keys used to encrypt the note. These are stored in encrypted format
     /// using the spender's outgoing viewing key, and allow the spender to
@@ -74,12 +88,17 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
     ) -> MerkleNote<J> {
         let (secret_key, public_key) = diffie_hellman_keys;
 
-        let encrypted_note = note.encrypt(&shared_secret(
+        let mut shared_key = shared_secret(
             &spender_key.sapling.jubjub,
             secret_key,
             &note.owner.transmission_key,
             public_key,
-        ));
+        );
+        let version = note.version();
+        let encrypted_note = note
+            .encrypt(version, &shared_key, public_key)
+            .expect("the note's version should fit its memo");
+        shared_key.zeroize();
 
         let mut key_bytes = [0; 64];
         note.owner
@@ -97,37 +116,31 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
         );
         let mut note_encryption_keys = [0; ENCRYPTED_SHARED_KEY_SIZE + aead::MAC_SIZE];
         aead::encrypt(&encryption_key, &key_bytes, &mut note_encryption_keys);
+        // The second half holds the ephemeral secret key
+        key_bytes.zeroize();
 
         MerkleNote {
             value_commitment: value_commitment.cm(&spender_key.sapling.jubjub).into(),
             note_commitment: note.commitment_point(),
             ephemeral_public_key: (*public_key).clone(),
+            version,
             encrypted_note,
             note_encryption_keys,
         }
     }
 
-    /// Load a MerkleNote from the given stream
-    pub fn read<R: io::Read>(mut reader: R, sapling: Arc<Sapling<J>>) -> io::Result<Self> {
-        let value_commitment = edwards::Point::<J, Unknown>::read(&mut reader, &sapling.jubjub)?;
-        let note_commitment = read_scalar(&mut reader).map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Unable to convert note commitment",
-            )
-        })?;
-
-        let public_key_non_prime =
-            edwards::Point::<J, Unknown>::read(&mut reader, &sapling.jubjub)?;
-        let ephemeral_public_key = public_key_non_prime
-            .as_prime_order(&sapling.jubjub)
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Unable to convert note commitment",
-                )
-            })?;
-        let mut encrypted_note = [0; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE];
+    /// Load a MerkleNote from the given stream.
+    ///
+    /// The note is serialized as the value commitment, note commitment,
+    /// ephemeral public key, encrypted note, and encrypted note encryption
+    /// keys. Notes with a versioned plaintext flag their note commitment and
+    /// follow it with the version (see `VERSIONED_NOTE_FLAG`), which
+    /// determines the size of the encrypted note.
+    pub fn read<R: io::Read>(mut reader: R, jubjub: &J::Params) -> io::Result<Self> {
+        let value_commitment = edwards::Point::<J, Unknown>::read(&mut reader, jubjub)?;
+        let (note_commitment, version) = read_note_commitment::<J, _>(&mut reader)?;
+        let ephemeral_public_key = read_ephemeral_public_key(&mut reader, jubjub)?;
+        let mut encrypted_note = vec![0; version.encrypted_size()];
         reader.read_exact(&mut encrypted_note[..])?;
         let mut note_encryption_keys = [0; ENCRYPTED_SHARED_KEY_SIZE + aead::MAC_SIZE];
         reader.read_exact(&mut note_encryption_keys[..])?;
@@ -135,6 +148,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
             value_commitment,
             note_commitment,
             ephemeral_public_key,
+            version,
             encrypted_note,
             note_encryption_keys,
         })
@@ -142,24 +156,49 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
 
     pub fn write<W: io::Write>(&self, mut writer: &mut W) -> io::Result<()> {
         self.value_commitment.write(&mut writer)?;
-        writer.write_all(self.note_commitment.to_repr().as_ref())?;
+        write_note_commitment::<J, _>(&mut writer, &self.note_commitment, self.version)?;
         self.ephemeral_public_key.write(&mut writer)?;
         writer.write_all(&self.encrypted_note[..])?;
         writer.write_all(&self.note_encryption_keys[..])?;
         Ok(())
     }
 
+    /// Version of the plaintext the note was encrypted with.
+    pub fn version(&self) -> NoteVersion {
+        self.version
+    }
+
+    pub(crate) fn encrypted_note(&self) -> &[u8] {
+        &self.encrypted_note
+    }
+
     pub fn merkle_hash(&self) -> MerkleNoteHash<J> {
         MerkleNoteHash::new(self.note_commitment)
     }
 
+    /// The parts of this note that a light client needs to find out whether
+    /// it owns the note. See `CompactMerkleNote`.
+    pub fn to_compact(&self) -> CompactMerkleNote<J> {
+        CompactMerkleNote {
+            note_commitment: self.note_commitment,
+            ephemeral_public_key: self.ephemeral_public_key.clone(),
+            version: self.version,
+            compact_encrypted_note: self.encrypted_note[..self.version.compact_size()].to_vec(),
+        }
+    }
+
     pub fn decrypt_note_for_owner(
         &self,
         owner_view_key: &IncomingViewKey<J>,
     ) -> Result<Note<J>, errors::NoteError> {
         let shared_secret = owner_view_key.shared_secret(&self.ephemeral_public_key);
-        let note =
-            Note::from_owner_encrypted(owner_view_key, &shared_secret, &self.encrypted_note)?;
+        let note = Note::from_owner_encrypted(
+            owner_view_key,
+            &shared_secret,
+            &self.ephemeral_public_key,
+            &self.note_commitment,
+            &self.encrypted_note,
+        )?;
         note.verify_commitment(self.note_commitment)?;
         Ok(note)
     }
@@ -168,6 +207,50 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
         &self,
         spender_key: &OutgoingViewKey<J>,
     ) -> Result<Note<J>, errors::NoteError> {
+        let (note, mut secret_key) = self.decrypt_note_and_secret_for_spender(spender_key)?;
+        zeroize_scalar(&mut secret_key);
+        Ok(note)
+    }
+
+    /// Decrypt a note sent by the owner of the view keys, with the details
+    /// needed to show it in their history of sent notes.
+    ///
+    /// The outgoing view key decrypts the note, and the incoming view key
+    /// tells whether the note was sent back to the same account.
+    pub fn decrypt_sent_note(
+        &self,
+        view_keys: &ViewKeys<J>,
+    ) -> Result<SentNoteInfo<J>, errors::NoteError> {
+        let (note, mut secret_key) =
+            self.decrypt_note_and_secret_for_spender(&view_keys.outgoing)?;
+        let valid_ephemeral_secret = note
+            .owner
+            .diversifier_point
+            .mul(secret_key, &note.verifier.jubjub)
+            == self.ephemeral_public_key;
+        zeroize_scalar(&mut secret_key);
+
+        let is_change = view_keys
+            .incoming
+            .public_address(&note.owner.diversifier.0)
+            .map_or(false, |address| address == note.owner);
+
+        Ok(SentNoteInfo {
+            recipient: note.owner.clone(),
+            value: note.value,
+            memo: note.memo,
+            valid_ephemeral_secret,
+            is_change,
+        })
+    }
+
+    /// Decrypt the note with the keys that the spender encrypted for
+    /// themselves, and return it along with the ephemeral secret key. The
+    /// caller has to zeroize the secret key once it is done with it.
+    fn decrypt_note_and_secret_for_spender(
+        &self,
+        spender_key: &OutgoingViewKey<J>,
+    ) -> Result<(Note<J>, J::Fs), errors::NoteError> {
         let encryption_key = calculate_key_for_encryption_keys(
             spender_key,
             &self.value_commitment,
@@ -185,25 +268,211 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> MerkleNote<J> {
         let transmission_key = PublicAddress::load_transmission_key(
             &spender_key.sapling.jubjub,
             &note_encryption_keys[..32],
-        )?;
-        let secret_key = read_scalar(&note_encryption_keys[32..])?;
-        let shared_key = shared_secret(
+        );
+        let secret_key: Result<J::Fs, _> = read_scalar(&note_encryption_keys[32..]);
+        // The second half holds the ephemeral secret key
+        note_encryption_keys.zeroize();
+        let mut secret_key = secret_key?;
+        let transmission_key = match transmission_key {
+            Ok(transmission_key) => transmission_key,
+            Err(e) => {
+                zeroize_scalar(&mut secret_key);
+                return Err(e.into());
+            }
+        };
+
+        let mut shared_key = shared_secret(
             &spender_key.sapling.jubjub,
             &secret_key,
             &transmission_key,
             &self.ephemeral_public_key,
         );
         let note = Note::from_spender_encrypted(
-            spender_key.sapling.clone(),
+            spender_key.sapling.verifier().clone(),
             transmission_key,
             &shared_key,
+            &self.ephemeral_public_key,
+            &self.note_commitment,
             &self.encrypted_note,
+        )
+        .and_then(|note| note.verify_commitment(self.note_commitment).map(|_| note));
+        shared_key.zeroize();
+
+        match note {
+            Ok(note) => Ok((note, secret_key)),
+            Err(e) => {
+                zeroize_scalar(&mut secret_key);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// What the spender of a note can see about it with their view keys. See
+/// `MerkleNote::decrypt_sent_note`.
+pub struct SentNoteInfo<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Address the note was sent to.
+    pub recipient: PublicAddress<J>,
+
+    pub value: u64,
+
+    pub memo: Memo,
+
+    /// Whether the ephemeral secret key that the spender stored with the note
+    /// generates the note's ephemeral public key from the recipient's
+    /// diversifier. If it doesn't, the recipient can't derive the key the
+    /// note was encrypted with.
+    pub valid_ephemeral_secret: bool,
+
+    /// Whether the note was sent to an address of the spender's own account,
+    /// as the change of a transaction is.
+    pub is_change: bool,
+}
+
+/// The part of a MerkleNote needed to detect and decrypt notes for the owner,
+/// like the compact outputs of Zcash's compact blocks.
+///
+/// Light clients scan every note on the chain for ones they own, so leaving
+/// out the value commitment, the spender's encrypted keys, and everything but
+/// the start of the ciphertext saves most of the bandwidth. The start of the
+/// ciphertext holds the diversifier, randomness, and value, which are enough
+/// to rebuild the note and check it against its commitment. The memo and mac
+/// are left out, so clients that want the memo have to fetch the full note.
+#[derive(Clone)]
+pub struct CompactMerkleNote<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// The hash of the note, committing to it's internal state
+    pub(crate) note_commitment: J::Fr,
+
+    /// Public part of ephemeral diffie-hellman key-pair, as in MerkleNote
+    pub(crate) ephemeral_public_key: edwards::Point<J, PrimeOrder>,
+
+    /// Version of the note plaintext, which determines how much of the
+    /// ciphertext is kept
+    pub(crate) version: NoteVersion,
+
+    /// The first `NoteVersion::compact_size` bytes of the encrypted note
+    pub(crate) compact_encrypted_note: Vec<u8>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> PartialEq for CompactMerkleNote<J> {
+    fn eq(&self, other: &CompactMerkleNote<J>) -> bool {
+        self.note_commitment == other.note_commitment
+            && self.ephemeral_public_key == other.ephemeral_public_key
+            && self.version == other.version
+            && self.compact_encrypted_note == other.compact_encrypted_note
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> CompactMerkleNote<J> {
+    /// Load a CompactMerkleNote from the given stream. The layout is the
+    /// same as that of a MerkleNote, without the value commitment, the end of
+    /// the encrypted note, and the note encryption keys.
+    pub fn read<R: io::Read>(mut reader: R, jubjub: &J::Params) -> io::Result<Self> {
+        let (note_commitment, version) = read_note_commitment::<J, _>(&mut reader)?;
+        let ephemeral_public_key = read_ephemeral_public_key(&mut reader, jubjub)?;
+        let mut compact_encrypted_note = vec![0; version.compact_size()];
+        reader.read_exact(&mut compact_encrypted_note[..])?;
+        Ok(CompactMerkleNote {
+            note_commitment,
+            ephemeral_public_key,
+            version,
+            compact_encrypted_note,
+        })
+    }
+
+    pub fn write<W: io::Write>(&self, mut writer: &mut W) -> io::Result<()> {
+        write_note_commitment::<J, _>(&mut writer, &self.note_commitment, self.version)?;
+        self.ephemeral_public_key.write(&mut writer)?;
+        writer.write_all(&self.compact_encrypted_note[..])?;
+        Ok(())
+    }
+
+    pub fn merkle_hash(&self) -> MerkleNoteHash<J> {
+        MerkleNoteHash::new(self.note_commitment)
+    }
+
+    /// Decrypt the note if it belongs to the owner of the view key.
+    ///
+    /// Without the mac, the decrypted note is only trusted because it matches
+    /// the note commitment. The memo isn't available in compact form, so the
+    /// note has an empty memo.
+    pub fn decrypt_note_for_owner(
+        &self,
+        owner_view_key: &IncomingViewKey<J>,
+    ) -> Result<Note<J>, errors::NoteError> {
+        let shared_secret = owner_view_key.shared_secret(&self.ephemeral_public_key);
+        let note = Note::from_owner_compact(
+            owner_view_key,
+            &shared_secret,
+            self.version,
+            &self.compact_encrypted_note,
         )?;
         note.verify_commitment(self.note_commitment)?;
         Ok(note)
     }
 }
 
+/// Read a note commitment written by `write_note_commitment`, along with the
+/// version of the note plaintext that it was written with.
+fn read_note_commitment<J: JubjubEngine + pairing::MultiMillerLoop, R: io::Read>(
+    mut reader: R,
+) -> io::Result<(J::Fr, NoteVersion)> {
+    let mut commitment_bytes = [0; 32];
+    reader.read_exact(&mut commitment_bytes)?;
+    let version = if commitment_bytes[31] & VERSIONED_NOTE_FLAG != 0 {
+        commitment_bytes[31] &= !VERSIONED_NOTE_FLAG;
+        match NoteVersion::from_byte(reader.read_u8()?) {
+            Some(version) if version != NoteVersion::V1 => version,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Unknown note version",
+                ))
+            }
+        }
+    } else {
+        NoteVersion::V1
+    };
+    let note_commitment = read_scalar(&commitment_bytes[..]).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Unable to convert note commitment",
+        )
+    })?;
+    Ok((note_commitment, version))
+}
+
+/// Write the note commitment. Versioned notes flag it with
+/// `VERSIONED_NOTE_FLAG` and follow it with their version byte, so version 1
+/// notes are written exactly as before versions existed.
+fn write_note_commitment<J: JubjubEngine + pairing::MultiMillerLoop, W: io::Write>(
+    mut writer: W,
+    note_commitment: &J::Fr,
+    version: NoteVersion,
+) -> io::Result<()> {
+    let mut commitment_bytes = [0; 32];
+    commitment_bytes.copy_from_slice(note_commitment.to_repr().as_ref());
+    if version == NoteVersion::V1 {
+        return writer.write_all(&commitment_bytes);
+    }
+    commitment_bytes[31] |= VERSIONED_NOTE_FLAG;
+    writer.write_all(&commitment_bytes)?;
+    writer.write_u8(version as u8)
+}
+
+fn read_ephemeral_public_key<J: JubjubEngine + pairing::MultiMillerLoop, R: io::Read>(
+    mut reader: R,
+    jubjub: &J::Params,
+) -> io::Result<edwards::Point<J, PrimeOrder>> {
+    let public_key_non_prime = edwards::Point::<J, Unknown>::read(&mut reader, jubjub)?;
+    public_key_non_prime.as_prime_order(jubjub).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Unable to convert ephemeral public key",
+        )
+    })
+}
+
 pub(crate) fn sapling_auth_path<J: JubjubEngine + pairing::MultiMillerLoop>(
     witness: &dyn WitnessTrait<J>,
 ) -> Vec<Option<(J::Fr, bool)>> {
@@ -270,21 +539,29 @@ fn calculate_key_for_encryption_keys<J: JubjubEngine + pairing::MultiMillerLoop>
 
 #[cfg(test)]
 mod test {
-    use super::MerkleNote;
+    use super::{CompactMerkleNote, MerkleNote, NOTE_ENCRYPTION_MINER_KEYS, VERSIONED_NOTE_FLAG};
     use crate::{
-        keys::SaplingKey,
-        note::{Memo, Note},
+        errors::NoteError,
+        keys::{shared_secret, SaplingKey},
+        note::{Memo, Note, NoteVersion},
         sapling_bls12,
+        serializing::hex_to_bytes,
     };
 
     use pairing::bls12_381::Bls12;
     use rand::prelude::*;
     use rand::{thread_rng, Rng};
+    use std::convert::TryFrom;
     use zcash_primitives::{
         jubjub::{fs::Fs, ToUniform},
         primitives::ValueCommitment,
     };
 
+    /// Size of a serialized note with a version 1 plaintext: the value
+    /// commitment, note commitment, and ephemeral public key, followed by the
+    /// encrypted note and note encryption keys.
+    const LEGACY_MERKLE_NOTE_SIZE: usize = 32 * 3 + 99 + 80;
+
     #[test]
     fn test_view_key_encryption() {
         let sapling = &*sapling_bls12::SAPLING;
@@ -294,7 +571,7 @@ mod test {
             sapling.clone(),
             receiver_key.generate_public_address(),
             42,
-            Memo([0; 32]),
+            Memo::default(),
         );
         let diffie_hellman_keys = note.owner.generate_diffie_hellman_keys(&sapling.jubjub);
 
@@ -318,6 +595,280 @@ mod test {
             .expect("should be able to decrypt note");
     }
 
+    #[test]
+    fn test_versioned_note_serialization() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+
+        for (memo, version) in &[
+            (Memo::try_from("short memo").unwrap(), NoteVersion::V1),
+            (Memo::try_from("short memo").unwrap(), NoteVersion::V3),
+            (
+                Memo::try_from("a much longer memo ".repeat(20)).unwrap(),
+                NoteVersion::V2,
+            ),
+        ] {
+            let note = Note::new(
+                sapling.clone(),
+                receiver_key.generate_public_address(),
+                42,
+                *memo,
+            );
+            let diffie_hellman_keys = note.owner.generate_diffie_hellman_keys(&sapling.jubjub);
+
+            let mut buffer = [0u8; 64];
+            thread_rng().fill(&mut buffer[..]);
+            let value_commitment = ValueCommitment::<Bls12> {
+                value: note.value,
+                randomness: Fs::to_uniform(&buffer[..]),
+            };
+
+            let mut merkle_note =
+                MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
+            if *version == NoteVersion::V1 {
+                // Notes encrypted before versioned plaintexts must still load
+                let shared_key = shared_secret(
+                    &sapling.jubjub,
+                    &diffie_hellman_keys.0,
+                    &note.owner.transmission_key,
+                    &diffie_hellman_keys.1,
+                );
+                merkle_note.encrypted_note = note
+                    .encrypt(*version, &shared_key, &diffie_hellman_keys.1)
+                    .expect("should be able to encrypt note");
+                merkle_note.version = *version;
+            }
+            assert_eq!(merkle_note.version(), *version);
+
+            let mut serialized = vec![];
+            merkle_note
+                .write(&mut serialized)
+                .expect("should be able to serialize note");
+            if *version == NoteVersion::V1 {
+                assert_eq!(serialized.len(), LEGACY_MERKLE_NOTE_SIZE);
+                assert_eq!(serialized[63] & VERSIONED_NOTE_FLAG, 0);
+            } else {
+                assert_eq!(
+                    serialized.len(),
+                    LEGACY_MERKLE_NOTE_SIZE + 1 + version.encrypted_size()
+                        - NoteVersion::V1.encrypted_size()
+                );
+                assert_eq!(serialized[63] & VERSIONED_NOTE_FLAG, VERSIONED_NOTE_FLAG);
+                assert_eq!(serialized[64], *version as u8);
+            }
+            // Trailing bytes must be left on the stream for the next reader
+            serialized.push(7);
+            let mut reader = &serialized[..];
+            let read_back =
+                MerkleNote::read(&mut reader, &sapling.jubjub).expect("should read note back");
+            assert_eq!(reader, &[7][..]);
+
+            assert!(read_back == merkle_note);
+            assert_eq!(read_back.version(), *version);
+            assert_eq!(read_back.encrypted_note, merkle_note.encrypted_note);
+            let decrypted = read_back
+                .decrypt_note_for_owner(receiver_key.incoming_view_key())
+                .expect("should be able to decrypt note");
+            assert_eq!(decrypted.memo(), *memo);
+            let decrypted = read_back
+                .decrypt_note_for_spender(spender_key.outgoing_view_key())
+                .expect("should be able to decrypt note");
+            assert_eq!(decrypted.memo(), *memo);
+
+            if *version != NoteVersion::V1 {
+                // Version 1 notes are never flagged
+                serialized[64] = NoteVersion::V1 as u8;
+                assert!(MerkleNote::read(&serialized[..], &sapling.jubjub).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_legacy_note() {
+        // The miners fee note of the genesis block, serialized before notes
+        // had versions
+        let serialized = hex_to_bytes(
+            "41dae018978597dd25cabe5167cc75eaf8ef3bd168e3351debf30af623fe5862\
+             ff8c374c432fa5d8c593b43737310d4745b98a23427ba545beb65cb8621be245\
+             6ff350f15728558bee168c247688b756d0be08efab3ab633d962f70d23a72717\
+             42eca93e8e419ae88505563d7cc840acd8185ea985d2c8e21fe824b97bebc0f3\
+             9e8f51cce0c539a81f0db9eef1ab1e928a56c2f0ac1e06e6b33189a0fc83ab97\
+             3d0684f9c6ac3dd68a8f200ca6c7cd47450195863d15c4bd7736ab14542cc6ca\
+             c949e14265616e7374616c6b206e6f746520656e6372797074696f6e206d696e\
+             6572206b65793030303030303030303030303030303030303030303030303030\
+             30303030303030303030303030303030303030",
+        )
+        .expect("should be valid hex");
+        assert_eq!(serialized.len(), LEGACY_MERKLE_NOTE_SIZE);
+
+        let sapling = &*sapling_bls12::SAPLING;
+        let merkle_note = MerkleNote::<Bls12>::read(&serialized[..], &sapling.jubjub)
+            .expect("should read legacy note");
+        assert_eq!(merkle_note.version(), NoteVersion::V1);
+        assert_eq!(
+            &merkle_note.encrypted_note[..],
+            &serialized[96..96 + NoteVersion::V1.encrypted_size()]
+        );
+        assert_eq!(
+            &merkle_note.note_encryption_keys[..],
+            &NOTE_ENCRYPTION_MINER_KEYS[..]
+        );
+
+        let mut written = vec![];
+        merkle_note
+            .write(&mut written)
+            .expect("should be able to serialize note");
+        assert_eq!(written, serialized);
+
+        let mut compact = vec![];
+        merkle_note
+            .to_compact()
+            .write(&mut compact)
+            .expect("should be able to serialize compact note");
+        assert_eq!(
+            &compact[..],
+            &serialized[32..96 + NoteVersion::V1.compact_size()]
+        );
+    }
+
+    #[test]
+    fn test_compact_note() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+
+        for memo in &[
+            Memo::try_from("short memo").unwrap(),
+            Memo::try_from("a much longer memo ".repeat(20)).unwrap(),
+        ] {
+            let note = Note::new(
+                sapling.clone(),
+                receiver_key.generate_public_address(),
+                42,
+                *memo,
+            );
+            let diffie_hellman_keys = note.owner.generate_diffie_hellman_keys(&sapling.jubjub);
+
+            let mut buffer = [0u8; 64];
+            thread_rng().fill(&mut buffer[..]);
+            let value_commitment = ValueCommitment::<Bls12> {
+                value: note.value,
+                randomness: Fs::to_uniform(&buffer[..]),
+            };
+
+            let merkle_note =
+                MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
+            let compact = merkle_note.to_compact();
+
+            let mut serialized = vec![];
+            compact
+                .write(&mut serialized)
+                .expect("should be able to serialize compact note");
+            assert_eq!(
+                serialized.len(),
+                1 + 64 + merkle_note.version().compact_size()
+            );
+            assert_eq!(serialized[31] & VERSIONED_NOTE_FLAG, VERSIONED_NOTE_FLAG);
+            let read_back = CompactMerkleNote::read(&serialized[..], &sapling.jubjub)
+                .expect("should read compact note back");
+            assert!(read_back == compact);
+            assert!(read_back.merkle_hash() == merkle_note.merkle_hash());
+
+            let decrypted = read_back
+                .decrypt_note_for_owner(receiver_key.incoming_view_key())
+                .expect("should be able to decrypt compact note");
+            assert_eq!(decrypted.value(), 42);
+            assert_eq!(decrypted.commitment(), note.commitment());
+            assert_eq!(decrypted.memo(), Memo::default());
+
+            assert!(read_back
+                .decrypt_note_for_owner(spender_key.incoming_view_key())
+                .is_err());
+
+            // Without a mac, tampering is only caught by the commitment
+            let mut tampered = read_back.clone();
+            let last = tampered.compact_encrypted_note.len() - 1;
+            tampered.compact_encrypted_note[last] ^= 1;
+            assert!(tampered != compact);
+            assert!(matches!(
+                tampered.decrypt_note_for_owner(receiver_key.incoming_view_key()),
+                Err(NoteError::InvalidCommitment)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_sent_note() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+
+        let merkle_note_for = |note: &Note<Bls12>, diffie_hellman_keys| {
+            let mut buffer = [0u8; 64];
+            thread_rng().fill(&mut buffer[..]);
+            let value_commitment = ValueCommitment::<Bls12> {
+                value: note.value,
+                randomness: Fs::to_uniform(&buffer[..]),
+            };
+            MerkleNote::new(&spender_key, note, &value_commitment, diffie_hellman_keys)
+        };
+
+        let payment = Note::new(
+            sapling.clone(),
+            receiver_key.generate_public_address(),
+            42,
+            Memo::try_from("payment").unwrap(),
+        );
+        let sent = merkle_note_for(
+            &payment,
+            &payment.owner.generate_diffie_hellman_keys(&sapling.jubjub),
+        )
+        .decrypt_sent_note(&spender_key.view_keys())
+        .expect("should be able to decrypt sent note");
+        assert_eq!(sent.recipient, payment.owner);
+        assert_eq!(sent.value, 42);
+        assert_eq!(sent.memo, payment.memo);
+        assert!(sent.valid_ephemeral_secret);
+        assert!(!sent.is_change);
+
+        let change = Note::new(
+            sapling.clone(),
+            spender_key.generate_public_address(),
+            8,
+            Memo::default(),
+        );
+        let sent = merkle_note_for(
+            &change,
+            &change.owner.generate_diffie_hellman_keys(&sapling.jubjub),
+        )
+        .decrypt_sent_note(&spender_key.view_keys())
+        .expect("should be able to decrypt sent note");
+        assert_eq!(sent.value, 8);
+        assert!(sent.valid_ephemeral_secret);
+        assert!(sent.is_change);
+
+        // An ephemeral public key that wasn't made from the recipient's
+        // diversifier still lets the spender decrypt, but not the recipient
+        let (secret_key, _) = payment.owner.generate_diffie_hellman_keys(&sapling.jubjub);
+        let wrong_public_key = receiver_key
+            .generate_public_address()
+            .diversifier_point
+            .mul(secret_key, &sapling.jubjub);
+        let merkle_note = merkle_note_for(&payment, &(secret_key, wrong_public_key));
+        let sent = merkle_note
+            .decrypt_sent_note(&spender_key.view_keys())
+            .expect("should be able to decrypt sent note");
+        assert!(!sent.valid_ephemeral_secret);
+        assert!(merkle_note
+            .decrypt_note_for_owner(receiver_key.incoming_view_key())
+            .is_err());
+
+        assert!(merkle_note
+            .decrypt_sent_note(&receiver_key.view_keys())
+            .is_err());
+    }
+
     #[test]
     fn test_receipt_invalid_commitment() {
         let sapling = &*sapling_bls12::SAPLING;
@@ -326,7 +877,7 @@ mod test {
             sapling.clone(),
             spender_key.generate_public_address(),
             42,
-            Memo([0; 32]),
+            Memo::default(),
         );
         let diffie_hellman_keys = note.owner.generate_diffie_hellman_keys(&sapling.jubjub);
 
@@ -4,47 +4,261 @@
 
 use super::{
     errors,
-    keys::{IncomingViewKey, PublicAddress, SaplingKey},
+    keys::{FullViewingKey, IncomingViewKey, PublicAddress, SaplingKey},
     nullifiers::Nullifier,
-    serializing::{aead, read_scalar, scalar_to_bytes},
-    Sapling,
+    serializing::{aead, bytes_to_hex, read_scalar, scalar_to_bytes},
+    Sapling, SaplingVerifier,
 };
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use ff::PrimeField;
 use rand::{thread_rng, Rng};
 use zcash_primitives::primitives::Note as SaplingNote;
 
-use std::{fmt, io, io::Read, sync::Arc};
+use std::{convert::TryFrom, fmt, io, io::Read, sync::Arc};
 use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder, ToUniform};
 
+/// Size of the original note plaintext, which has no version byte and a 32
+/// byte memo. See `NoteVersion`.
 pub const ENCRYPTED_NOTE_SIZE: usize = 83;
 
+/// Size of a version 2 note plaintext: a version byte, followed by the same
+/// fields as the original plaintext but with the full memo.
+pub const ENCRYPTED_NOTE_V2_SIZE: usize = 1 + 11 + 32 + 8 + MEMO_SIZE;
+
+/// Size of a version 3 note plaintext: a version byte, followed by the same
+/// fields as the original plaintext, including its 32 byte memo.
+pub const ENCRYPTED_NOTE_V3_SIZE: usize = 1 + 11 + 32 + 8 + LEGACY_MEMO_SIZE;
+
+pub const MEMO_SIZE: usize = 512;
+
+/// Memos that only use this many leading bytes can be stored in the original
+/// note plaintext.
+pub const LEGACY_MEMO_SIZE: usize = 32;
+
+// Leading bytes that identify the kind of a typed memo. Like ZIP-302, they
+// are all bytes that can't start a UTF-8 string, so they don't clash with
+// memos that were created from plain text.
+const MEMO_TAG_TEXT: u8 = 0xf5;
+const MEMO_TAG_PAYMENT_REFERENCE: u8 = 0xf6;
+const MEMO_TAG_BINARY: u8 = 0xff;
+
+/// Text and binary memos are stored after the tag and a two byte length.
+pub const MAX_MEMO_CONTENTS_SIZE: usize = MEMO_SIZE - 3;
+
+/// Layout of the plaintext that a note is encrypted as, and how it is
+/// encrypted.
+///
+/// Version 1 is the original layout, which has no version byte and only
+/// holds the first `LEGACY_MEMO_SIZE` bytes of the memo. Later versions start
+/// with their version byte, and all versions have different sizes, so they
+/// are told apart by the size of the ciphertext.
+///
+/// From version 2, the ciphertext also authenticates the ephemeral public key
+/// and note commitment of the output it belongs to, so it can't be copied
+/// into another output and still decrypt. Version 2 holds the full memo, and
+/// version 3 only the first `LEGACY_MEMO_SIZE` bytes of it, so notes with
+/// short memos stay about as small as version 1 notes. New notes use the
+/// smaller of the two that fits their memo (see `Note::version`). Version 1
+/// is only read, for notes created before the others existed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteVersion {
+    V1 = 1,
+    V2 = 2,
+    V3 = 3,
+}
+
+impl NoteVersion {
+    const ALL: [NoteVersion; 3] = [NoteVersion::V1, NoteVersion::V2, NoteVersion::V3];
+
+    /// Size of the note plaintext in this version.
+    pub fn plaintext_size(self) -> usize {
+        match self {
+            NoteVersion::V1 => ENCRYPTED_NOTE_SIZE,
+            NoteVersion::V2 => ENCRYPTED_NOTE_V2_SIZE,
+            NoteVersion::V3 => ENCRYPTED_NOTE_V3_SIZE,
+        }
+    }
+
+    /// Size of the encrypted note, including the MAC, in this version.
+    pub fn encrypted_size(self) -> usize {
+        self.plaintext_size() + aead::MAC_SIZE
+    }
+
+    /// Size of the part of the plaintext before the memo, which is all that is
+    /// needed to check a note's commitment. See `CompactMerkleNote`.
+    pub fn compact_size(self) -> usize {
+        self.plaintext_size() - self.memo_size()
+    }
+
+    /// Number of memo bytes stored in this version.
+    fn memo_size(self) -> usize {
+        match self {
+            NoteVersion::V1 | NoteVersion::V3 => LEGACY_MEMO_SIZE,
+            NoteVersion::V2 => MEMO_SIZE,
+        }
+    }
+
+    pub(crate) fn from_encrypted_size(size: usize) -> Option<NoteVersion> {
+        NoteVersion::ALL
+            .iter()
+            .copied()
+            .find(|version| version.encrypted_size() == size)
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Option<NoteVersion> {
+        NoteVersion::ALL
+            .iter()
+            .copied()
+            .find(|version| *version as u8 == byte)
+    }
+}
+
 /// Memo field on a Note. Used to encode transaction IDs or other information
 /// about the transaction.
+///
+/// The memo is always 512 bytes, padded with zeroes. Use `Memo::new` to
+/// encode one of the kinds of `MemoContents` into it, and `contents` to
+/// decode it again.
 #[derive(Shrinkwrap, Debug, Clone, Copy, PartialEq)]
-pub struct Memo(pub [u8; 32]);
+pub struct Memo(pub [u8; MEMO_SIZE]);
+
+/// The decoded contents of a memo. Each kind is stored with a leading tag
+/// byte, except for empty memos, which are all zeroes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoContents {
+    Empty,
+
+    /// UTF-8 text of up to 509 bytes.
+    Text(String),
+
+    /// Arbitrary bytes, up to 509 of them.
+    Binary(Vec<u8>),
+
+    /// A number identifying the payment to the recipient, such as the deposit
+    /// ID that an exchange assigns to a customer.
+    PaymentReference(u64),
+
+    /// A memo without a recognized tag, such as those created before memos
+    /// had tags. Trailing zeroes are left out. Raw contents can't
+    /// start with one of the tag bytes, since they would decode as that kind.
+    Raw(Vec<u8>),
+}
+
+impl Memo {
+    /// Encode the given contents into a memo. Fails if the contents don't
+    /// fit, instead of truncating them, or if raw contents start with a tag
+    /// byte.
+    pub fn new(contents: MemoContents) -> Result<Memo, errors::NoteError> {
+        let mut memo = [0; MEMO_SIZE];
+        match contents {
+            MemoContents::Empty => {}
+            MemoContents::Text(text) => {
+                Self::write_tagged(&mut memo, MEMO_TAG_TEXT, text.as_bytes())?
+            }
+            MemoContents::Binary(bytes) => Self::write_tagged(&mut memo, MEMO_TAG_BINARY, &bytes)?,
+            MemoContents::PaymentReference(reference) => {
+                memo[0] = MEMO_TAG_PAYMENT_REFERENCE;
+                LittleEndian::write_u64(&mut memo[1..9], reference);
+            }
+            MemoContents::Raw(bytes) => {
+                if bytes.len() > MEMO_SIZE {
+                    return Err(errors::NoteError::MemoTooLong);
+                }
+                if let Some(&MEMO_TAG_TEXT)
+                | Some(&MEMO_TAG_PAYMENT_REFERENCE)
+                | Some(&MEMO_TAG_BINARY) = bytes.first()
+                {
+                    return Err(errors::NoteError::InvalidMemo);
+                }
+                memo[..bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+        Ok(Memo(memo))
+    }
+
+    /// Decode the contents of this memo. Memos that don't have a valid tag are
+    /// returned as `Raw`.
+    pub fn contents(&self) -> MemoContents {
+        let tagged = &self.0[3..];
+        let length = LittleEndian::read_u16(&self.0[1..3]) as usize;
+        match self.0[0] {
+            0 if self.0.iter().all(|b| *b == 0) => MemoContents::Empty,
+            MEMO_TAG_TEXT if length <= MAX_MEMO_CONTENTS_SIZE => {
+                match std::str::from_utf8(&tagged[..length]) {
+                    Ok(text) => MemoContents::Text(text.to_string()),
+                    Err(_) => self.raw_contents(),
+                }
+            }
+            MEMO_TAG_BINARY if length <= MAX_MEMO_CONTENTS_SIZE => {
+                MemoContents::Binary(tagged[..length].to_vec())
+            }
+            MEMO_TAG_PAYMENT_REFERENCE => {
+                MemoContents::PaymentReference(LittleEndian::read_u64(&self.0[1..9]))
+            }
+            _ => self.raw_contents(),
+        }
+    }
+
+    /// Whether everything past the first `LEGACY_MEMO_SIZE` bytes is zero, so
+    /// the memo can be stored in a version 1 note plaintext.
+    pub fn fits_legacy_size(&self) -> bool {
+        self.0[LEGACY_MEMO_SIZE..].iter().all(|b| *b == 0)
+    }
+
+    fn raw_contents(&self) -> MemoContents {
+        let length = self.0.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        MemoContents::Raw(self.0[..length].to_vec())
+    }
 
-impl From<&str> for Memo {
-    fn from(string: &str) -> Self {
-        let memo_as_bytes = string.as_bytes();
-        let num_to_clone = std::cmp::min(memo_as_bytes.len(), 32);
-        let mut memo_bytes = [0; 32];
-        memo_bytes[..num_to_clone].clone_from_slice(&memo_as_bytes[..num_to_clone]);
-        Memo(memo_bytes)
+    fn write_tagged(
+        memo: &mut [u8; MEMO_SIZE],
+        tag: u8,
+        bytes: &[u8],
+    ) -> Result<(), errors::NoteError> {
+        if bytes.len() > MAX_MEMO_CONTENTS_SIZE {
+            return Err(errors::NoteError::MemoTooLong);
+        }
+        memo[0] = tag;
+        LittleEndian::write_u16(&mut memo[1..3], bytes.len() as u16);
+        memo[3..3 + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl Default for Memo {
+    fn default() -> Self {
+        Memo([0; MEMO_SIZE])
     }
 }
 
-impl From<String> for Memo {
-    fn from(string: String) -> Self {
-        Memo::from(string.as_str())
+/// Create a text memo. Fails with `MemoTooLong` if the text doesn't fit.
+impl TryFrom<&str> for Memo {
+    type Error = errors::NoteError;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Memo::new(MemoContents::Text(string.to_string()))
+    }
+}
+
+impl TryFrom<String> for Memo {
+    type Error = errors::NoteError;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        Memo::new(MemoContents::Text(string))
     }
 }
 
 impl fmt::Display for Memo {
-    /// This can be lossy because it assumes that the
-    /// memo is in valid UTF-8 format.
+    /// Raw memos can be displayed lossily, because this assumes that they are
+    /// in valid UTF-8 format. Binary memos are displayed as hex.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", String::from_utf8_lossy(&self.0))
+        match self.contents() {
+            MemoContents::Empty => Ok(()),
+            MemoContents::Text(text) => write!(f, "{}", text),
+            MemoContents::Binary(bytes) => write!(f, "{}", bytes_to_hex(&bytes)),
+            MemoContents::PaymentReference(reference) => write!(f, "{}", reference),
+            MemoContents::Raw(bytes) => write!(f, "{}", String::from_utf8_lossy(&bytes)),
+        }
     }
 }
 
@@ -57,7 +271,9 @@ impl fmt::Display for Memo {
 /// to hold those funds.
 #[derive(Clone)]
 pub struct Note<J: JubjubEngine + pairing::MultiMillerLoop> {
-    pub(crate) sapling: Arc<Sapling<J>>,
+    /// The note only needs the jubjub parameters, so it keeps the verifier
+    /// instead of the whole Sapling instance.
+    pub(crate) verifier: Arc<SaplingVerifier<J>>,
     /// A public address for the owner of the note. One owner can have multiple public addresses,
     /// each associated with a different diversifier.
     pub(crate) owner: PublicAddress<J>,
@@ -87,7 +303,7 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
         let randomness: J::Fs = J::Fs::to_uniform(&buffer[..]);
 
         Self {
-            sapling,
+            verifier: sapling.verifier().clone(),
             owner,
             value,
             randomness,
@@ -99,22 +315,34 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     ///
     /// You probably don't want to use this unless you are transmitting
     /// across nodejs threads in memory.
+    ///
+    /// Notes written before the memo was extended only have the first
+    /// `LEGACY_MEMO_SIZE` bytes of the memo. Those are still accepted, as long
+    /// as the stream ends right after them.
     pub fn read<R: io::Read>(
         mut reader: R,
         sapling: Arc<Sapling<J>>,
     ) -> Result<Self, errors::SaplingKeyError> {
-        let owner = PublicAddress::read(sapling.clone(), &mut reader)?;
+        let owner = PublicAddress::read(&sapling.jubjub, &mut reader)?;
         let value = reader.read_u64::<LittleEndian>()?;
         let randomness: J::Fs = read_scalar(&mut reader)?;
 
-        let mut memo_vec = vec![];
-        let mut memo = Memo([0; 32]);
-        reader.read_to_end(&mut memo_vec)?;
-        assert_eq!(memo_vec.len(), 32);
-        memo.0.copy_from_slice(&memo_vec[..]);
+        let mut memo = Memo::default();
+        reader.read_exact(&mut memo.0[..LEGACY_MEMO_SIZE])?;
+        let mut extended_memo = Vec::with_capacity(MEMO_SIZE - LEGACY_MEMO_SIZE);
+        (&mut reader)
+            .take((MEMO_SIZE - LEGACY_MEMO_SIZE) as u64)
+            .read_to_end(&mut extended_memo)?;
+        match extended_memo.len() {
+            0 => {}
+            length if length == MEMO_SIZE - LEGACY_MEMO_SIZE => {
+                memo.0[LEGACY_MEMO_SIZE..].copy_from_slice(&extended_memo)
+            }
+            _ => return Err(errors::SaplingKeyError::IOError),
+        }
 
         Ok(Self {
-            sapling,
+            verifier: sapling.verifier().clone(),
             owner,
             value,
             randomness,
@@ -143,18 +371,26 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     /// derived from the owner's public key.
     ///
     /// This function allows the owner to decrypt the note using the derived
-    /// shared secret and their own view key.
+    /// shared secret and their own view key. The ephemeral public key and note
+    /// commitment are those of the output the note was stored on; notes from
+    /// version 2 on only decrypt if they match.
     pub fn from_owner_encrypted(
         owner_view_key: &'a IncomingViewKey<J>,
         shared_secret: &[u8; 32],
-        encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+        ephemeral_public_key: &edwards::Point<J, PrimeOrder>,
+        note_commitment: &J::Fr,
+        encrypted_bytes: &[u8],
     ) -> Result<Self, errors::NoteError> {
-        let (diversifier_bytes, randomness, value, memo) =
-            Note::<J>::decrypt_note_parts(shared_secret, encrypted_bytes)?;
+        let (diversifier_bytes, randomness, value, memo) = Note::<J>::decrypt_note_parts(
+            shared_secret,
+            ephemeral_public_key,
+            note_commitment,
+            encrypted_bytes,
+        )?;
         let owner = owner_view_key.public_address(&diversifier_bytes)?;
 
         Ok(Note {
-            sapling: owner_view_key.sapling.clone(),
+            verifier: owner_view_key.sapling.verifier().clone(),
             owner,
             value,
             randomness,
@@ -172,15 +408,21 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     /// This function allows the owner to decrypt the note using the derived
     /// shared secret and their own view key.
     pub(crate) fn from_spender_encrypted(
-        sapling: Arc<Sapling<J>>,
+        verifier: Arc<SaplingVerifier<J>>,
         transmission_key: edwards::Point<J, PrimeOrder>,
         shared_secret: &[u8; 32],
-        encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+        ephemeral_public_key: &edwards::Point<J, PrimeOrder>,
+        note_commitment: &J::Fr,
+        encrypted_bytes: &[u8],
     ) -> Result<Self, errors::NoteError> {
-        let (diversifier_bytes, randomness, value, memo) =
-            Note::<J>::decrypt_note_parts(shared_secret, encrypted_bytes)?;
+        let (diversifier_bytes, randomness, value, memo) = Note::<J>::decrypt_note_parts(
+            shared_secret,
+            ephemeral_public_key,
+            note_commitment,
+            encrypted_bytes,
+        )?;
         let (diversifier, diversifier_point) =
-            PublicAddress::load_diversifier(&sapling.jubjub, &diversifier_bytes[..])?;
+            PublicAddress::load_diversifier(&verifier.jubjub, &diversifier_bytes[..])?;
         let owner = PublicAddress {
             diversifier,
             diversifier_point,
@@ -188,7 +430,7 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
         };
 
         Ok(Note {
-            sapling,
+            verifier,
             owner,
             value,
             randomness,
@@ -196,6 +438,37 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
         })
     }
 
+    /// Create a note from the start of its encrypted representation, given
+    /// the owner's view key. See `CompactMerkleNote`.
+    ///
+    /// The memo isn't part of the compact ciphertext, so the note has an empty
+    /// memo. The compact ciphertext doesn't have a mac either, so the caller
+    /// has to verify the note against its commitment before trusting it.
+    pub(crate) fn from_owner_compact(
+        owner_view_key: &'a IncomingViewKey<J>,
+        shared_secret: &[u8; 32],
+        version: NoteVersion,
+        compact_bytes: &[u8],
+    ) -> Result<Self, errors::NoteError> {
+        if compact_bytes.len() != version.compact_size() {
+            return Err(errors::NoteError::InvalidVersion);
+        }
+        let mut plaintext_bytes = vec![0; compact_bytes.len()];
+        aead::decrypt_prefix(shared_secret, compact_bytes, &mut plaintext_bytes);
+
+        let (diversifier_bytes, randomness, value) =
+            Note::<J>::read_note_parts(&plaintext_bytes[..], version)?;
+        let owner = owner_view_key.public_address(&diversifier_bytes)?;
+
+        Ok(Note {
+            verifier: owner_view_key.sapling.verifier().clone(),
+            owner,
+            value,
+            randomness,
+            memo: Memo::default(),
+        })
+    }
+
     pub fn value(&self) -> u64 {
         self.value
     }
@@ -208,20 +481,60 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
         self.owner.clone()
     }
 
+    /// The plaintext version this note should be encrypted with. Notes whose
+    /// memo fits in the original plaintext size use version 3, so they are
+    /// about the same size as notes created before the memo was extended.
+    pub fn version(&self) -> NoteVersion {
+        if self.memo.fits_legacy_size() {
+            NoteVersion::V3
+        } else {
+            NoteVersion::V2
+        }
+    }
+
     /// Send encrypted form of the note, which is what gets publicly stored on
     /// the tree. Only someone with the incoming viewing key for the note can
     /// actually read the contents.
-    pub fn encrypt(&self, shared_secret: &[u8; 32]) -> [u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE] {
-        let mut bytes_to_encrypt = [0; ENCRYPTED_NOTE_SIZE];
-        bytes_to_encrypt[..11].copy_from_slice(&self.owner.diversifier.0[..]);
-        bytes_to_encrypt[11..43].clone_from_slice(self.randomness.to_repr().as_ref());
+    ///
+    /// The ephemeral public key is the one the shared secret was derived with.
+    /// New notes should use their `version`; version 1 is only there for
+    /// compatibility. Fails with `MemoTooLong` if the memo doesn't fit in the
+    /// given version.
+    pub fn encrypt(
+        &self,
+        version: NoteVersion,
+        shared_secret: &[u8; 32],
+        ephemeral_public_key: &edwards::Point<J, PrimeOrder>,
+    ) -> Result<Vec<u8>, errors::NoteError> {
+        if version.memo_size() < MEMO_SIZE && !self.memo.fits_legacy_size() {
+            return Err(errors::NoteError::MemoTooLong);
+        }
 
-        LittleEndian::write_u64_into(&[self.value], &mut bytes_to_encrypt[43..51]);
-        bytes_to_encrypt[51..].copy_from_slice(&self.memo[..]);
-        let mut encrypted_bytes = [0; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE];
-        aead::encrypt(shared_secret, &bytes_to_encrypt, &mut encrypted_bytes);
+        let mut bytes_to_encrypt = Vec::with_capacity(version.plaintext_size());
+        if version != NoteVersion::V1 {
+            bytes_to_encrypt.push(version as u8);
+        }
+        bytes_to_encrypt.extend_from_slice(&self.owner.diversifier.0[..]);
+        bytes_to_encrypt.extend_from_slice(self.randomness.to_repr().as_ref());
+        bytes_to_encrypt
+            .write_u64::<LittleEndian>(self.value)
+            .expect("should be able to write to a vector");
+        bytes_to_encrypt.extend_from_slice(&self.memo[..version.memo_size()]);
+
+        let mut encrypted_bytes = vec![0; version.encrypted_size()];
+        match version {
+            NoteVersion::V1 => {
+                aead::encrypt(shared_secret, &bytes_to_encrypt, &mut encrypted_bytes)
+            }
+            NoteVersion::V2 | NoteVersion::V3 => aead::encrypt_with_aad(
+                shared_secret,
+                &associated_data(ephemeral_public_key, &self.commitment_point()),
+                &bytes_to_encrypt,
+                &mut encrypted_bytes,
+            ),
+        }
 
-        encrypted_bytes
+        Ok(encrypted_bytes)
     }
 
     /// Compute the nullifier for this note, given the private key of its owner.
@@ -230,11 +543,22 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     /// only at the time the note is spent. This key is collected in a massive
     /// 'nullifier set', preventing double-spend.
     pub fn nullifier(&self, private_key: &SaplingKey<J>, position: u64) -> Nullifier {
+        self.nullifier_from_view_key(&private_key.full_viewing_key(), position)
+    }
+
+    /// Compute the nullifier for this note, given the full viewing key of its
+    /// owner. Watch-only wallets use this to see when their notes are spent
+    /// without having the spending key.
+    pub fn nullifier_from_view_key(
+        &self,
+        view_key: &FullViewingKey<J>,
+        position: u64,
+    ) -> Nullifier {
         let mut result = [0; 32];
         let result_as_vec = self.sapling_note().nf(
-            &private_key.sapling_viewing_key(),
+            &view_key.sapling_viewing_key(),
             position,
-            &self.sapling.jubjub,
+            &self.verifier.jubjub,
         );
         assert_eq!(result_as_vec.len(), 32);
         result[0..32].copy_from_slice(&result_as_vec[0..32]);
@@ -254,7 +578,7 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     /// The owner can publish this value to commit to the fact that the note
     /// exists, without revealing any of the values on the note until later.
     pub(crate) fn commitment_point(&self) -> J::Fr {
-        self.sapling_note().cm(&self.sapling.jubjub)
+        self.sapling_note().cm(&self.verifier.jubjub)
     }
 
     /// Verify that the note's commitment matches the one passed in
@@ -266,25 +590,51 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
         }
     }
 
+    /// Decrypt the fields of an encrypted note. The plaintext version is
+    /// determined by the size of the ciphertext, and checked against the
+    /// version byte for versions that have one.
     fn decrypt_note_parts(
         shared_secret: &[u8; 32],
-        encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+        ephemeral_public_key: &edwards::Point<J, PrimeOrder>,
+        note_commitment: &J::Fr,
+        encrypted_bytes: &[u8],
     ) -> Result<([u8; 11], J::Fs, u64, Memo), errors::NoteError> {
-        let mut plaintext_bytes = [0; ENCRYPTED_NOTE_SIZE];
-        aead::decrypt(shared_secret, encrypted_bytes, &mut plaintext_bytes)?;
+        let version = NoteVersion::from_encrypted_size(encrypted_bytes.len())
+            .ok_or(errors::NoteError::InvalidVersion)?;
+        let mut plaintext_bytes = vec![0; version.plaintext_size()];
+        match version {
+            NoteVersion::V1 => aead::decrypt(shared_secret, encrypted_bytes, &mut plaintext_bytes)?,
+            NoteVersion::V2 | NoteVersion::V3 => aead::decrypt_with_aad(
+                shared_secret,
+                &associated_data(ephemeral_public_key, note_commitment),
+                encrypted_bytes,
+                &mut plaintext_bytes,
+            )?,
+        }
 
         let mut reader = plaintext_bytes[..].as_ref();
+        let (diversifier_bytes, randomness, value) =
+            Note::<J>::read_note_parts(&mut reader, version)?;
+        let mut memo = Memo::default();
+        reader.read_exact(&mut memo.0[..version.memo_size()])?;
+        Ok((diversifier_bytes, randomness, value, memo))
+    }
+
+    /// Read the fields that come before the memo in a note plaintext of the
+    /// given version.
+    fn read_note_parts<R: io::Read>(
+        mut reader: R,
+        version: NoteVersion,
+    ) -> Result<([u8; 11], J::Fs, u64), errors::NoteError> {
+        if version != NoteVersion::V1 && reader.read_u8()? != version as u8 {
+            return Err(errors::NoteError::InvalidVersion);
+        }
         let mut diversifier_bytes = [0; 11];
         reader.read_exact(&mut diversifier_bytes[..])?;
 
         let randomness: J::Fs = read_scalar(&mut reader)?;
         let value = reader.read_u64::<LittleEndian>()?;
-        let mut memo_vec = vec![];
-        let mut memo = Memo([0; 32]);
-        reader.read_to_end(&mut memo_vec)?;
-        assert_eq!(memo_vec.len(), 32);
-        memo.0.copy_from_slice(&memo_vec[..]);
-        Ok((diversifier_bytes, randomness, value, memo))
+        Ok((diversifier_bytes, randomness, value))
     }
 
     /// The zcash_primitives version of the Note API is kind of klunky with
@@ -297,28 +647,53 @@ impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> Note<J> {
     fn sapling_note(&self) -> SaplingNote<J> {
         SaplingNote {
             value: self.value,
-            g_d: self.owner.diversifier.g_d(&self.sapling.jubjub).unwrap(),
+            g_d: self.owner.diversifier.g_d(&self.verifier.jubjub).unwrap(),
             pk_d: self.owner.transmission_key.clone(),
             r: self.randomness,
         }
     }
 }
 
+/// The associated data that versioned note ciphertexts authenticate: the
+/// ephemeral public key and note commitment of their output.
+fn associated_data<J: JubjubEngine + pairing::MultiMillerLoop>(
+    ephemeral_public_key: &edwards::Point<J, PrimeOrder>,
+    note_commitment: &J::Fr,
+) -> [u8; 64] {
+    let mut associated_data = [0; 64];
+    ephemeral_public_key
+        .write(&mut associated_data[..32])
+        .expect("should be able to write point to bytes");
+    associated_data[32..].copy_from_slice(note_commitment.to_repr().as_ref());
+    associated_data
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Memo, Note};
+    use super::{
+        Memo, MemoContents, Note, NoteVersion, ENCRYPTED_NOTE_SIZE, ENCRYPTED_NOTE_V2_SIZE,
+        ENCRYPTED_NOTE_V3_SIZE,
+    };
+    use crate::errors::NoteError;
     use crate::{
         keys::{shared_secret, SaplingKey},
         sapling_bls12,
+        serializing::aead,
     };
     use pairing::bls12_381::Bls12;
+    use std::convert::TryFrom;
 
     #[test]
     fn test_plaintext_serialization() {
         let sapling = &*sapling_bls12::SAPLING;
         let owner_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
         let public_address = owner_key.generate_public_address();
-        let note = Note::new(sapling.clone(), public_address, 42, "serialize me".into());
+        let note = Note::new(
+            sapling.clone(),
+            public_address,
+            42,
+            Memo::try_from("serialize me").unwrap(),
+        );
         let mut serialized = Vec::new();
         note.write(&mut serialized)
             .expect("Should serialize cleanly");
@@ -337,6 +712,33 @@ mod test {
         assert_eq!(serialized, serialized2)
     }
 
+    #[test]
+    fn test_legacy_plaintext_serialization() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let owner_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let public_address = owner_key.generate_public_address();
+        let note = Note::new(
+            sapling.clone(),
+            public_address,
+            42,
+            Memo::try_from("serialize me").unwrap(),
+        );
+        let mut serialized = Vec::new();
+        note.write(&mut serialized)
+            .expect("Should serialize cleanly");
+
+        // Notes used to be written with only the first 32 bytes of the memo
+        let legacy = &serialized[..115];
+        let note2 = Note::read(legacy, sapling.clone()).expect("It should deserialize cleanly");
+        assert_eq!(note2.owner.public_address(), note.owner.public_address());
+        assert_eq!(note2.value, 42);
+        assert_eq!(note2.randomness, note.randomness);
+        assert_eq!(note2.memo, note.memo);
+
+        assert!(Note::read(&serialized[..200], sapling.clone()).is_err());
+        assert!(Note::read(&serialized[..100], sapling.clone()).is_err());
+    }
+
     #[test]
     fn test_note_encryption() {
         let sapling = &*sapling_bls12::SAPLING;
@@ -349,8 +751,11 @@ mod test {
             &public_address.transmission_key,
             &dh_public,
         );
-        let note = Note::new(sapling.clone(), public_address, 42, Memo([0; 32]));
-        let encryption_result = note.encrypt(&public_shared_secret);
+        let note = Note::new(sapling.clone(), public_address, 42, Memo::default());
+        let note_commitment = note.commitment_point();
+        let encryption_result = note
+            .encrypt(note.version(), &public_shared_secret, &dh_public)
+            .expect("Should be able to encrypt note");
 
         let private_shared_secret = owner_key.incoming_view_key().shared_secret(&dh_public);
         assert_eq!(private_shared_secret, public_shared_secret);
@@ -358,6 +763,8 @@ mod test {
         let restored_note = Note::from_owner_encrypted(
             owner_key.incoming_view_key(),
             &private_shared_secret,
+            &dh_public,
+            &note_commitment,
             &encryption_result,
         )
         .expect("Should be able to decrypt bytes");
@@ -369,9 +776,11 @@ mod test {
         assert!(note.memo == restored_note.memo);
 
         let spender_decrypted = Note::from_spender_encrypted(
-            sapling.clone(),
+            sapling.verifier().clone(),
             note.owner.transmission_key.clone(),
             &public_shared_secret,
+            &dh_public,
+            &note_commitment,
             &encryption_result,
         )
         .expect("Should be able to load from transmission key");
@@ -386,12 +795,216 @@ mod test {
 
     #[test]
     fn construct_memo_from_string() {
-        let memo = Memo::from("a memo");
-        assert_eq!(&memo.0[..6], b"a memo");
+        let memo = Memo::try_from("a memo").expect("should encode memo");
+        assert_eq!(memo.contents(), MemoContents::Text("a memo".to_string()));
         let string = "a memo".to_string();
-        let memo = Memo::from(&*string);
-        assert_eq!(&memo.0[..6], b"a memo");
-        let memo = Memo::from(string);
-        assert_eq!(&memo.0[..6], b"a memo");
+        assert_eq!(Memo::try_from(&*string).unwrap(), memo);
+        assert_eq!(Memo::try_from(string).unwrap(), memo);
+        assert!(matches!(
+            Memo::try_from("x".repeat(510)),
+            Err(NoteError::MemoTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_typed_memos() {
+        let contents = vec![
+            MemoContents::Empty,
+            MemoContents::Text("deposit for order 1234".to_string()),
+            MemoContents::Text("".to_string()),
+            MemoContents::Binary(vec![1, 2, 3, 0, 0]),
+            MemoContents::PaymentReference(1 << 40),
+            MemoContents::Raw(b"a memo".to_vec()),
+        ];
+        for content in contents {
+            let memo = Memo::new(content.clone()).expect("should encode memo");
+            assert_eq!(memo.contents(), content);
+        }
+
+        assert_eq!(Memo::default().contents(), MemoContents::Empty);
+        assert_eq!(
+            Memo::new(MemoContents::PaymentReference(42))
+                .unwrap()
+                .to_string(),
+            "42"
+        );
+        assert_eq!(
+            Memo::new(MemoContents::Text("hello".to_string()))
+                .unwrap()
+                .to_string(),
+            "hello"
+        );
+
+        // Memos created before they had tags are plain bytes
+        let mut legacy = Memo::default();
+        legacy.0[..6].copy_from_slice(b"a memo");
+        assert!(matches!(legacy.contents(), MemoContents::Raw(_)));
+        assert!(legacy.to_string().starts_with("a memo"));
+
+        assert!(matches!(
+            Memo::new(MemoContents::Text("x".repeat(510))),
+            Err(NoteError::MemoTooLong)
+        ));
+        assert!(matches!(
+            Memo::new(MemoContents::Binary(vec![0; 510])),
+            Err(NoteError::MemoTooLong)
+        ));
+        assert!(matches!(
+            Memo::new(MemoContents::Raw(vec![0; 513])),
+            Err(NoteError::MemoTooLong)
+        ));
+        // Raw contents that start with a tag wouldn't decode as raw again
+        for tag in &[0xf5, 0xf6, 0xff] {
+            assert!(matches!(
+                Memo::new(MemoContents::Raw(vec![*tag, 1, 0, b'x'])),
+                Err(NoteError::InvalidMemo)
+            ));
+        }
+        let long = Memo::new(MemoContents::Text("x".repeat(509))).expect("should encode memo");
+        assert_eq!(long.contents(), MemoContents::Text("x".repeat(509)));
+    }
+
+    #[test]
+    fn test_versioned_note_encryption() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let owner_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let public_address = owner_key.generate_public_address();
+        let (dh_secret, dh_public) = public_address.generate_diffie_hellman_keys(&sapling.jubjub);
+        let shared_secret = shared_secret(
+            &sapling.jubjub,
+            &dh_secret,
+            &public_address.transmission_key,
+            &dh_public,
+        );
+
+        let short_note = Note::new(
+            sapling.clone(),
+            public_address.clone(),
+            42,
+            Memo::try_from("short").unwrap(),
+        );
+        assert_eq!(short_note.version(), NoteVersion::V3);
+        let short_encrypted = short_note
+            .encrypt(short_note.version(), &shared_secret, &dh_public)
+            .expect("short memo should fit in a version 3 note");
+        assert_eq!(
+            short_encrypted.len(),
+            ENCRYPTED_NOTE_V3_SIZE + aead::MAC_SIZE
+        );
+        let legacy_encrypted = short_note
+            .encrypt(NoteVersion::V1, &shared_secret, &dh_public)
+            .expect("short memo should fit in a version 1 note");
+        assert_eq!(legacy_encrypted.len(), ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE);
+
+        let invoice = MemoContents::Text("invoice 1234, return to: ".repeat(10));
+        let long_note = Note::new(
+            sapling.clone(),
+            public_address,
+            42,
+            Memo::new(invoice.clone()).unwrap(),
+        );
+        assert_eq!(long_note.version(), NoteVersion::V2);
+        for version in &[NoteVersion::V1, NoteVersion::V3] {
+            assert!(matches!(
+                long_note.encrypt(*version, &shared_secret, &dh_public),
+                Err(NoteError::MemoTooLong)
+            ));
+        }
+        let long_encrypted = long_note
+            .encrypt(long_note.version(), &shared_secret, &dh_public)
+            .expect("should be able to encrypt note");
+        assert_eq!(
+            long_encrypted.len(),
+            ENCRYPTED_NOTE_V2_SIZE + aead::MAC_SIZE
+        );
+
+        let short_commitment = short_note.commitment_point();
+        for (note, encrypted) in &[
+            (short_note.clone(), short_encrypted),
+            (short_note, legacy_encrypted),
+            (long_note, long_encrypted),
+        ] {
+            let restored = Note::from_owner_encrypted(
+                owner_key.incoming_view_key(),
+                &shared_secret,
+                &dh_public,
+                &note.commitment_point(),
+                encrypted,
+            )
+            .expect("should be able to decrypt note");
+            assert_eq!(restored.memo, note.memo);
+            assert_eq!(restored.value, note.value);
+            assert_eq!(restored.commitment(), note.commitment());
+        }
+        assert_eq!(
+            Note::from_owner_encrypted(
+                owner_key.incoming_view_key(),
+                &shared_secret,
+                &dh_public,
+                &short_commitment,
+                &[0; 101]
+            )
+            .unwrap_err()
+            .to_string(),
+            "InvalidVersion"
+        );
+    }
+
+    #[test]
+    fn test_note_encryption_binds_output() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let owner_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let public_address = owner_key.generate_public_address();
+        let (dh_secret, dh_public) = public_address.generate_diffie_hellman_keys(&sapling.jubjub);
+        let shared_secret = shared_secret(
+            &sapling.jubjub,
+            &dh_secret,
+            &public_address.transmission_key,
+            &dh_public,
+        );
+        let note = Note::new(
+            sapling.clone(),
+            public_address.clone(),
+            42,
+            Memo::try_from("bound").unwrap(),
+        );
+        let other_note = Note::new(
+            sapling.clone(),
+            public_address.clone(),
+            42,
+            Memo::try_from("bound").unwrap(),
+        );
+        let (_, other_public) = public_address.generate_diffie_hellman_keys(&sapling.jubjub);
+        let long_note = Note::new(
+            sapling.clone(),
+            public_address.clone(),
+            42,
+            Memo::try_from("bound ".repeat(10)).unwrap(),
+        );
+
+        let decrypt = |encrypted: &[u8], public_key, commitment| {
+            Note::from_owner_encrypted(
+                owner_key.incoming_view_key(),
+                &shared_secret,
+                public_key,
+                commitment,
+                encrypted,
+            )
+        };
+
+        for note in &[&note, &long_note] {
+            let encrypted = note
+                .encrypt(note.version(), &shared_secret, &dh_public)
+                .unwrap();
+            assert!(decrypt(&encrypted, &dh_public, &note.commitment_point()).is_ok());
+            assert!(decrypt(&encrypted, &dh_public, &other_note.commitment_point()).is_err());
+            assert!(decrypt(&encrypted, &other_public, &note.commitment_point()).is_err());
+        }
+
+        // Legacy notes aren't bound to their output
+        let legacy = note
+            .encrypt(NoteVersion::V1, &shared_secret, &dh_public)
+            .unwrap();
+        assert!(decrypt(&legacy, &other_public, &other_note.commitment_point()).is_ok());
     }
 }
@@ -0,0 +1,127 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::TreeStore;
+use crate::{errors::MerkleTreeError, serializing::read_scalar};
+use ff::PrimeField;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+use zcash_primitives::jubjub::JubjubEngine;
+use zcash_proofs::circuit::sapling::TREE_DEPTH;
+
+/// Number of bytes used to store each hash.
+const HASH_SIZE: u64 = 32;
+
+/// TreeStore that persists nodes to a directory on disk, so a tree can be
+/// reloaded across restarts.
+///
+/// Each level of the tree is kept in its own append-only file of 32 byte
+/// hashes, so a node is found by seeking to `index * 32` in the file for its
+/// depth. Nothing is cached in memory besides the number of nodes per level.
+pub struct FileTreeStore<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// One file per level of the tree, from the leaves up to the root. Reading
+    /// a node moves the file's cursor, so readers sharing the store take
+    /// turns with each file.
+    files: Vec<Mutex<File>>,
+
+    /// Number of complete hashes in each of the files.
+    counts: Vec<u64>,
+
+    phantom: PhantomData<J>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> FileTreeStore<J> {
+    /// Open the store kept in the given directory, creating it if it doesn't
+    /// exist yet.
+    ///
+    /// If the process stopped while a leaf was being appended, the levels
+    /// above it may be missing hashes. In that case the store is truncated
+    /// back to the last size at which every level was consistent, and the
+    /// missing leaves need to be appended again.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MerkleTreeError> {
+        let path = path.as_ref();
+        fs::create_dir_all(path)?;
+
+        let mut files = Vec::with_capacity(TREE_DEPTH + 1);
+        let mut counts = Vec::with_capacity(TREE_DEPTH + 1);
+        for depth in 0..=TREE_DEPTH {
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .open(path.join(format!("nodes-{:02}", depth)))?;
+            counts.push(file.metadata()?.len() / HASH_SIZE);
+            files.push(Mutex::new(file));
+        }
+
+        // A tree of n leaves has n >> depth nodes at each depth, so each level
+        // bounds how many leaves can be trusted.
+        let leaf_count = counts
+            .iter()
+            .enumerate()
+            .map(|(depth, count)| match depth {
+                0 => *count,
+                _ => ((count + 1) << depth) - 1,
+            })
+            .min()
+            .expect("store should have at least one level");
+
+        let mut store = FileTreeStore {
+            files,
+            counts,
+            phantom: PhantomData,
+        };
+        store.truncate(leaf_count)?;
+        Ok(store)
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> TreeStore<J> for FileTreeStore<J> {
+    fn leaf_count(&self) -> u64 {
+        self.counts[0]
+    }
+
+    fn get_node(&self, depth: usize, index: u64) -> Result<Option<J::Fr>, MerkleTreeError> {
+        if depth > TREE_DEPTH || index >= self.counts[depth] {
+            return Ok(None);
+        }
+        // A reader that panicked can't have left the file in a bad state, since
+        // each read starts by seeking.
+        let mut file = self.files[depth]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        file.seek(SeekFrom::Start(index * HASH_SIZE))?;
+        let hash = read_scalar(&mut *file)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Unable to read node hash"))?;
+        Ok(Some(hash))
+    }
+
+    fn put_node(&mut self, depth: usize, index: u64, hash: J::Fr) -> Result<(), MerkleTreeError> {
+        if depth > TREE_DEPTH || index != self.counts[depth] {
+            return Err(MerkleTreeError::InvalidPosition);
+        }
+        self.files[depth]
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(hash.to_repr().as_ref())?;
+        self.counts[depth] += 1;
+        Ok(())
+    }
+
+    fn truncate(&mut self, leaf_count: u64) -> Result<(), MerkleTreeError> {
+        for (depth, file) in self.files.iter_mut().enumerate() {
+            let count = std::cmp::min(leaf_count >> depth, self.counts[depth]);
+            file.get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .set_len(count * HASH_SIZE)?;
+            self.counts[depth] = count;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::{NoteCommitmentTree, TreeStore};
+use crate::{
+    errors::MerkleTreeError,
+    witness::{Witness, WitnessNode},
+    MerkleNoteHash, Sapling,
+};
+use std::sync::Arc;
+use zcash_primitives::jubjub::JubjubEngine;
+use zcash_proofs::circuit::sapling::TREE_DEPTH;
+
+/// Witness to a single leaf that can be kept up to date as new leaves are
+/// appended to the tree, without access to the rest of the tree.
+///
+/// This follows the IncrementalWitness in librustzcash. The siblings on the
+/// left of the leaf's path never change, so they are recorded once. The
+/// siblings on the right are filled in from the bottom up as leaves are
+/// appended: `filled` holds the right siblings that are complete, and
+/// `cursor` holds the frontier of the one that is currently being built, at
+/// `cursor_depth`. Right siblings that don't contain any leaves yet take the
+/// hash of the node on the path, like they do in the tree itself.
+#[derive(Clone)]
+pub struct IncrementalWitness<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Used to combine child hashes into their parent.
+    sapling: Arc<Sapling<J>>,
+
+    /// Position of the witnessed leaf in the tree.
+    position: u64,
+
+    /// Hash of the witnessed leaf.
+    leaf: J::Fr,
+
+    /// Number of leaves in the tree, including all the appended ones.
+    tree_size: u64,
+
+    /// Hash of the left sibling at each depth where the leaf's path is a
+    /// right child, or None where it is a left child.
+    left_siblings: Vec<Option<J::Fr>>,
+
+    /// Hashes of the complete right siblings, in order of increasing depth.
+    filled: Vec<J::Fr>,
+
+    /// Frontier of the right sibling that is partially filled, if any.
+    cursor: Option<Frontier<J>>,
+
+    /// Depth of the right sibling that the cursor is building.
+    cursor_depth: usize,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> IncrementalWitness<J> {
+    /// Construct a witness to the leaf at `position` in the given tree, which
+    /// can then be advanced independently of the tree.
+    pub fn from_tree<S: TreeStore<J>>(
+        tree: &NoteCommitmentTree<J, S>,
+        position: u64,
+    ) -> Result<Self, MerkleTreeError> {
+        let tree_size = tree.size();
+        if position >= tree_size {
+            return Err(MerkleTreeError::InvalidPosition);
+        }
+
+        let mut witness = IncrementalWitness {
+            sapling: tree.sapling.clone(),
+            position,
+            leaf: tree.stored_node(0, position)?,
+            tree_size,
+            left_siblings: vec![None; TREE_DEPTH],
+            filled: vec![],
+            cursor: None,
+            cursor_depth: 0,
+        };
+
+        let mut right_siblings_done = false;
+        for depth in 0..TREE_DEPTH {
+            let index = position >> depth;
+            if index % 2 == 1 {
+                witness.left_siblings[depth] = Some(tree.stored_node(depth, index - 1)?);
+                continue;
+            }
+            if right_siblings_done {
+                continue;
+            }
+
+            let start = (index + 1) << depth;
+            let end = (index + 2) << depth;
+            if end <= tree_size {
+                witness.filled.push(tree.stored_node(depth, index + 1)?);
+            } else {
+                if start < tree_size {
+                    witness.cursor = Some(Frontier::from_tree(tree, start, tree_size, depth)?);
+                    witness.cursor_depth = depth;
+                }
+                right_siblings_done = true;
+            }
+        }
+
+        Ok(witness)
+    }
+
+    /// Position of the witnessed leaf in the tree.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Number of leaves in the tree this witness is currently valid for.
+    pub fn tree_size(&self) -> u64 {
+        self.tree_size
+    }
+
+    /// Advance the witness with a leaf that was appended to the tree.
+    ///
+    /// Leaves must be appended in the same order as they were added to the
+    /// tree, or the witness won't match its root.
+    pub fn append(&mut self, hash: &MerkleNoteHash<J>) -> Result<(), MerkleTreeError> {
+        if let Some(cursor) = self.cursor.as_mut() {
+            cursor.append(&self.sapling, hash.0);
+            if cursor.size == 1 << self.cursor_depth {
+                let sibling = cursor
+                    .root(&self.sapling, self.cursor_depth)
+                    .expect("complete cursor should have a root");
+                self.filled.push(sibling);
+                self.cursor = None;
+            }
+        } else {
+            let depth = self.next_depth().ok_or(MerkleTreeError::TreeFull)?;
+            if depth == 0 {
+                self.filled.push(hash.0);
+            } else {
+                let mut cursor = Frontier::new();
+                cursor.append(&self.sapling, hash.0);
+                self.cursor = Some(cursor);
+                self.cursor_depth = depth;
+            }
+        }
+
+        self.tree_size += 1;
+        Ok(())
+    }
+
+    /// Get the root hash of the tree at its current size.
+    pub fn root_hash(&self) -> J::Fr {
+        self.witness().root_hash
+    }
+
+    /// Construct the authentication path for the leaf against the current
+    /// root of the tree.
+    pub fn witness(&self) -> Witness<J> {
+        let mut current_hash = self.leaf;
+        let mut filled = self.filled.iter();
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+
+        for depth in 0..TREE_DEPTH {
+            current_hash = match self.left_siblings[depth] {
+                Some(sibling_hash) => {
+                    auth_path.push(WitnessNode::Right(sibling_hash));
+                    MerkleNoteHash::combine_hash(&self.sapling, depth, &sibling_hash, &current_hash)
+                }
+                None => {
+                    let sibling_hash = match (filled.next(), &self.cursor) {
+                        (Some(hash), _) => *hash,
+                        (None, Some(cursor)) if self.cursor_depth == depth => cursor
+                            .root(&self.sapling, depth)
+                            .expect("cursor should not be empty"),
+                        _ => current_hash,
+                    };
+                    auth_path.push(WitnessNode::Left(sibling_hash));
+                    MerkleNoteHash::combine_hash(&self.sapling, depth, &current_hash, &sibling_hash)
+                }
+            };
+        }
+
+        Witness {
+            hasher: self.sapling.clone(),
+            tree_size: self.tree_size as usize,
+            root_hash: current_hash,
+            auth_path,
+        }
+    }
+
+    /// Depth of the next right sibling that new leaves will be appended to,
+    /// once the cursor has been filled.
+    fn next_depth(&self) -> Option<usize> {
+        (0..TREE_DEPTH)
+            .filter(|depth| self.left_siblings[*depth].is_none())
+            .nth(self.filled.len())
+    }
+}
+
+/// The right edge of a tree that is being built one leaf at a time.
+///
+/// For each bit that is set in the size of the tree, there is a complete
+/// subtree at that depth waiting for a right sibling. Those are the only
+/// hashes needed to append new leaves and to calculate the root.
+#[derive(Clone)]
+struct Frontier<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Number of leaves in the tree.
+    size: u64,
+
+    /// Hash of the complete subtree at each depth that is waiting for a
+    /// right sibling.
+    ommers: Vec<Option<J::Fr>>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Frontier<J> {
+    fn new() -> Self {
+        Frontier {
+            size: 0,
+            ommers: vec![None; TREE_DEPTH + 1],
+        }
+    }
+
+    /// Load the frontier of the subtree containing the leaves from `start` up
+    /// to (but excluding) `end` in the given tree. `start` is the first leaf of
+    /// a subtree at `depth`, which the leaves don't fill yet.
+    fn from_tree<S: TreeStore<J>>(
+        tree: &NoteCommitmentTree<J, S>,
+        start: u64,
+        end: u64,
+        depth: usize,
+    ) -> Result<Self, MerkleTreeError> {
+        let mut frontier = Frontier::new();
+        frontier.size = end - start;
+
+        let mut offset = start;
+        for ommer_depth in (0..depth).rev() {
+            if frontier.size & (1 << ommer_depth) != 0 {
+                frontier.ommers[ommer_depth] =
+                    Some(tree.stored_node(ommer_depth, offset >> ommer_depth)?);
+                offset += 1 << ommer_depth;
+            }
+        }
+
+        Ok(frontier)
+    }
+
+    fn append(&mut self, sapling: &Sapling<J>, hash: J::Fr) {
+        let mut carry = hash;
+        for depth in 0..TREE_DEPTH {
+            match self.ommers[depth].take() {
+                Some(left) => carry = MerkleNoteHash::combine_hash(sapling, depth, &left, &carry),
+                None => {
+                    self.ommers[depth] = Some(carry);
+                    self.size += 1;
+                    return;
+                }
+            }
+        }
+        self.ommers[TREE_DEPTH] = Some(carry);
+        self.size += 1;
+    }
+
+    /// Calculate the hash of the subtree at `depth` containing these leaves,
+    /// or None if the frontier is empty. Nodes without a right sibling are
+    /// combined with themselves.
+    fn root(&self, sapling: &Sapling<J>, depth: usize) -> Option<J::Fr> {
+        let mut current_hash = None;
+        for ommer_depth in 0..depth {
+            current_hash = match (self.ommers[ommer_depth], current_hash) {
+                (Some(left), Some(right)) => Some(MerkleNoteHash::combine_hash(
+                    sapling,
+                    ommer_depth,
+                    &left,
+                    &right,
+                )),
+                (Some(node), None) | (None, Some(node)) => Some(MerkleNoteHash::combine_hash(
+                    sapling,
+                    ommer_depth,
+                    &node,
+                    &node,
+                )),
+                (None, None) => None,
+            };
+        }
+        current_hash.or(self.ommers[depth])
+    }
+}
@@ -0,0 +1,231 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Append-only Merkle tree of note commitments.
+//!
+//! This mirrors the tree that the node maintains in TypeScript, so the roots
+//! and witnesses calculated here match the ones stored on the chain.
+
+use super::{
+    errors::MerkleTreeError,
+    witness::{Witness, WitnessNode},
+    MerkleNoteHash, Sapling,
+};
+use std::sync::Arc;
+use zcash_primitives::jubjub::JubjubEngine;
+use zcash_proofs::circuit::sapling::TREE_DEPTH;
+
+mod file_store;
+pub use file_store::FileTreeStore;
+mod incremental_witness;
+pub use incremental_witness::IncrementalWitness;
+mod root_history;
+pub use root_history::RootHistory;
+mod store;
+pub use store::{MemoryTreeStore, TreeStore};
+
+#[cfg(test)]
+mod tests;
+
+/// A fixed depth Merkle tree containing the hashes of every note that has
+/// been added to the chain, in the order they were added.
+///
+/// The tree always has `TREE_DEPTH` levels. A node that doesn't have a right
+/// child yet is combined with itself, so the root of a tree containing a
+/// single leaf is that leaf hashed with itself all the way up.
+///
+/// Only the hashes of complete subtrees are stored. Hashes along the right
+/// edge of the tree depend on how many leaves are in it, and are recalculated
+/// on demand. This is what allows roots to be calculated for past sizes of the
+/// tree, and makes rewinding it cheap.
+///
+/// The stored hashes are kept in a TreeStore, which holds them in memory
+/// unless the tree is constructed with a different store.
+pub struct NoteCommitmentTree<
+    J: JubjubEngine + pairing::MultiMillerLoop,
+    S: TreeStore<J> = MemoryTreeStore<J>,
+> {
+    /// Used to combine child hashes into their parent.
+    sapling: Arc<Sapling<J>>,
+
+    /// Hashes of the leaves and of the complete subtrees above them.
+    store: S,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> NoteCommitmentTree<J> {
+    /// Construct a new, empty tree that is kept in memory.
+    pub fn new(sapling: Arc<Sapling<J>>) -> Self {
+        Self::with_store(sapling, MemoryTreeStore::new())
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop, S: TreeStore<J>> NoteCommitmentTree<J, S> {
+    /// Construct a tree from the nodes in the given store. The store may
+    /// already contain leaves, for example if it was loaded from disk.
+    pub fn with_store(sapling: Arc<Sapling<J>>, store: S) -> Self {
+        NoteCommitmentTree { sapling, store }
+    }
+
+    /// Get the store that holds this tree's nodes.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Number of leaves currently in the tree.
+    pub fn size(&self) -> u64 {
+        self.store.leaf_count()
+    }
+
+    /// Get the hash of the leaf at the given position, if there is one.
+    pub fn leaf(&self, position: u64) -> Result<Option<MerkleNoteHash<J>>, MerkleTreeError> {
+        Ok(self.store.get_node(0, position)?.map(MerkleNoteHash::new))
+    }
+
+    /// Add a new leaf to the right side of the tree, returning its position.
+    ///
+    /// Any subtrees completed by the new leaf are hashed and stored, so this
+    /// performs at most `TREE_DEPTH` hashes.
+    pub fn append(&mut self, hash: &MerkleNoteHash<J>) -> Result<u64, MerkleTreeError> {
+        let position = self.size();
+        if position >= 1 << TREE_DEPTH {
+            return Err(MerkleTreeError::TreeFull);
+        }
+        self.store.put_node(0, position, hash.0)?;
+
+        let mut index = position;
+        let mut current_hash = hash.0;
+        for depth in 0..TREE_DEPTH {
+            if index % 2 == 0 {
+                break;
+            }
+            let left = self.stored_node(depth, index - 1)?;
+            current_hash = MerkleNoteHash::combine_hash(&self.sapling, depth, &left, &current_hash);
+            index /= 2;
+            self.store.put_node(depth + 1, index, current_hash)?;
+        }
+
+        Ok(position)
+    }
+
+    /// Get the root hash of the tree as it is now.
+    ///
+    /// Fails if the tree is empty.
+    pub fn root_hash(&self) -> Result<J::Fr, MerkleTreeError> {
+        self.past_root(self.size())
+    }
+
+    /// Calculate what the root hash of the tree was when it contained `size`
+    /// leaves.
+    ///
+    /// Fails if `size` is zero or larger than the current size of the tree.
+    pub fn past_root(&self, size: u64) -> Result<J::Fr, MerkleTreeError> {
+        if size == 0 || size > self.size() {
+            return Err(MerkleTreeError::InvalidTreeSize);
+        }
+        self.node_hash(TREE_DEPTH, 0, size)?
+            .ok_or(MerkleTreeError::MissingNode)
+    }
+
+    /// Check whether `root_hash` was the root of the tree when it contained
+    /// `tree_size` leaves, as claimed by the anchor of a SpendProof.
+    ///
+    /// Use a RootHistory instead when checking many spends, so that each past
+    /// root only gets calculated once.
+    pub fn is_valid_anchor(
+        &self,
+        root_hash: &J::Fr,
+        tree_size: u32,
+    ) -> Result<bool, MerkleTreeError> {
+        let tree_size = tree_size as u64;
+        if tree_size == 0 || tree_size > self.size() {
+            return Ok(false);
+        }
+        Ok(self.past_root(tree_size)? == *root_hash)
+    }
+
+    /// Remove leaves from the right side of the tree until it contains `size`
+    /// leaves.
+    pub fn rewind(&mut self, size: u64) -> Result<(), MerkleTreeError> {
+        if size > self.size() {
+            return Err(MerkleTreeError::InvalidTreeSize);
+        }
+        self.store.truncate(size)
+    }
+
+    /// Construct a witness for the leaf at the given position against the
+    /// current root of the tree.
+    ///
+    /// Fails if there is no leaf at that position.
+    pub fn witness(&self, position: u64) -> Result<Witness<J>, MerkleTreeError> {
+        let size = self.size();
+        if position >= size {
+            return Err(MerkleTreeError::InvalidPosition);
+        }
+
+        let mut current_hash = self.stored_node(0, position)?;
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+        for depth in 0..TREE_DEPTH {
+            let index = position >> depth;
+            let sibling_hash = self
+                .node_hash(depth, index ^ 1, size)?
+                .unwrap_or(current_hash);
+            current_hash = if index % 2 == 0 {
+                auth_path.push(WitnessNode::Left(sibling_hash));
+                MerkleNoteHash::combine_hash(&self.sapling, depth, &current_hash, &sibling_hash)
+            } else {
+                auth_path.push(WitnessNode::Right(sibling_hash));
+                MerkleNoteHash::combine_hash(&self.sapling, depth, &sibling_hash, &current_hash)
+            };
+        }
+
+        Ok(Witness {
+            hasher: self.sapling.clone(),
+            tree_size: size as usize,
+            root_hash: current_hash,
+            auth_path,
+        })
+    }
+
+    /// Calculate the hash of the node at `index` on the level `depth` steps
+    /// above the leaves, as it was when the tree contained `size` leaves.
+    ///
+    /// Returns None if that node didn't contain any leaves yet. Complete
+    /// subtrees are looked up in the store; partial ones can only appear on
+    /// the right edge of the tree, so at most one of them is calculated per
+    /// level.
+    fn node_hash(
+        &self,
+        depth: usize,
+        index: u64,
+        size: u64,
+    ) -> Result<Option<J::Fr>, MerkleTreeError> {
+        if index << depth >= size {
+            return Ok(None);
+        }
+        if (index + 1) << depth <= size {
+            return self.stored_node(depth, index).map(Some);
+        }
+
+        let left = self
+            .node_hash(depth - 1, index * 2, size)?
+            .ok_or(MerkleTreeError::MissingNode)?;
+        let right = self
+            .node_hash(depth - 1, index * 2 + 1, size)?
+            .unwrap_or(left);
+        Ok(Some(MerkleNoteHash::combine_hash(
+            &self.sapling,
+            depth - 1,
+            &left,
+            &right,
+        )))
+    }
+
+    /// Get a hash that the store is expected to contain, failing if it's not
+    /// there.
+    fn stored_node(&self, depth: usize, index: u64) -> Result<J::Fr, MerkleTreeError> {
+        self.store
+            .get_node(depth, index)?
+            .ok_or(MerkleTreeError::MissingNode)
+    }
+}
@@ -0,0 +1,93 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::{NoteCommitmentTree, TreeStore};
+use crate::errors::MerkleTreeError;
+use std::collections::BTreeMap;
+use zcash_primitives::jubjub::JubjubEngine;
+
+/// Cache of the roots a NoteCommitmentTree had at past sizes, used to check
+/// the anchors of incoming spends.
+///
+/// Every SpendProof carries the root hash and size of the tree its witness
+/// was calculated against. Calculating a past root takes a hash per level of
+/// the tree, and the spends in a block tend to share a handful of anchors, so
+/// the roots are remembered after they are first calculated.
+///
+/// The history doesn't watch the tree for changes. If the tree is rewound,
+/// the history needs to be rewound to the same size, since the roots above
+/// that size will be different once new leaves are appended.
+pub struct RootHistory<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Maximum number of roots to remember. When full, the roots for the
+    /// smallest tree sizes are forgotten first.
+    capacity: usize,
+
+    /// Known root hashes, by tree size.
+    roots: BTreeMap<u64, J::Fr>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> RootHistory<J> {
+    /// Construct an empty history that remembers up to `capacity` roots.
+    pub fn new(capacity: usize) -> Self {
+        RootHistory {
+            capacity,
+            roots: BTreeMap::new(),
+        }
+    }
+
+    /// Number of roots currently remembered.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Get the root the tree had when it contained `tree_size` leaves,
+    /// calculating and remembering it if it isn't known yet.
+    pub fn past_root<S: TreeStore<J>>(
+        &mut self,
+        tree: &NoteCommitmentTree<J, S>,
+        tree_size: u64,
+    ) -> Result<J::Fr, MerkleTreeError> {
+        if let Some(root) = self.roots.get(&tree_size) {
+            return Ok(*root);
+        }
+
+        let root = tree.past_root(tree_size)?;
+        if self.capacity > 0 {
+            if self.roots.len() >= self.capacity {
+                let smallest = *self.roots.keys().next().expect("history is not empty");
+                self.roots.remove(&smallest);
+            }
+            self.roots.insert(tree_size, root);
+        }
+        Ok(root)
+    }
+
+    /// Check whether `root_hash` was the root of the tree when it contained
+    /// `tree_size` leaves. This is the check miners perform on the
+    /// `root_hash` and `tree_size` of each SpendProof.
+    ///
+    /// Sizes of zero or larger than the tree are never valid anchors.
+    pub fn is_valid_anchor<S: TreeStore<J>>(
+        &mut self,
+        tree: &NoteCommitmentTree<J, S>,
+        root_hash: &J::Fr,
+        tree_size: u32,
+    ) -> Result<bool, MerkleTreeError> {
+        let tree_size = tree_size as u64;
+        if tree_size == 0 || tree_size > tree.size() {
+            return Ok(false);
+        }
+        Ok(self.past_root(tree, tree_size)? == *root_hash)
+    }
+
+    /// Forget the roots for any size larger than `tree_size`. Call this
+    /// whenever the tree is rewound.
+    pub fn rewind(&mut self, tree_size: u64) {
+        self.roots.split_off(&(tree_size + 1));
+    }
+}
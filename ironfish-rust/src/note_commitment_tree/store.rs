@@ -0,0 +1,82 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::errors::MerkleTreeError;
+use zcash_primitives::jubjub::JubjubEngine;
+
+/// Storage for the nodes of a NoteCommitmentTree.
+///
+/// Nodes are addressed by their depth (0 for the leaves) and their index from
+/// the left on that level. The tree only ever stores the hashes of complete
+/// subtrees, so each level grows strictly left to right and a store only has
+/// to support appending to the end of a level, or truncating the whole tree
+/// back to a smaller size.
+pub trait TreeStore<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Number of leaves in the store. This is the number of nodes at depth 0.
+    fn leaf_count(&self) -> u64;
+
+    /// Get the hash of the node at the given depth and index, or None if it
+    /// hasn't been stored.
+    fn get_node(&self, depth: usize, index: u64) -> Result<Option<J::Fr>, MerkleTreeError>;
+
+    /// Store the hash of the node at the given depth and index. The index must
+    /// be the next unused index on that level.
+    fn put_node(&mut self, depth: usize, index: u64, hash: J::Fr) -> Result<(), MerkleTreeError>;
+
+    /// Remove all nodes that are not part of a tree containing `leaf_count`
+    /// leaves.
+    fn truncate(&mut self, leaf_count: u64) -> Result<(), MerkleTreeError>;
+}
+
+/// TreeStore that keeps all the nodes in memory. This is the default store
+/// for a NoteCommitmentTree.
+pub struct MemoryTreeStore<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// `nodes[depth]` contains the hashes stored on that level, left to right.
+    nodes: Vec<Vec<J::Fr>>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> MemoryTreeStore<J> {
+    pub fn new() -> Self {
+        MemoryTreeStore { nodes: vec![] }
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Default for MemoryTreeStore<J> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> TreeStore<J> for MemoryTreeStore<J> {
+    fn leaf_count(&self) -> u64 {
+        self.nodes.first().map_or(0, |leaves| leaves.len() as u64)
+    }
+
+    fn get_node(&self, depth: usize, index: u64) -> Result<Option<J::Fr>, MerkleTreeError> {
+        Ok(self
+            .nodes
+            .get(depth)
+            .and_then(|level| level.get(index as usize))
+            .copied())
+    }
+
+    fn put_node(&mut self, depth: usize, index: u64, hash: J::Fr) -> Result<(), MerkleTreeError> {
+        if self.nodes.len() <= depth {
+            self.nodes.resize(depth + 1, vec![]);
+        }
+        let level = &mut self.nodes[depth];
+        if index != level.len() as u64 {
+            return Err(MerkleTreeError::InvalidPosition);
+        }
+        level.push(hash);
+        Ok(())
+    }
+
+    fn truncate(&mut self, leaf_count: u64) -> Result<(), MerkleTreeError> {
+        for (depth, level) in self.nodes.iter_mut().enumerate() {
+            level.truncate((leaf_count >> depth) as usize);
+        }
+        Ok(())
+    }
+}
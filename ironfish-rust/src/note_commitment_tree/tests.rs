@@ -0,0 +1,346 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::{
+    FileTreeStore, IncrementalWitness, MemoryTreeStore, NoteCommitmentTree, RootHistory, TreeStore,
+};
+use crate::{
+    merkle_note::position as witness_position,
+    sapling_bls12,
+    serializing::hex_to_bytes,
+    witness::{WitnessNode, WitnessTrait},
+    MerkleNoteHash,
+};
+use pairing::bls12_381::{Bls12, Fr};
+use rand::random;
+use std::{env, fs, path::PathBuf, sync::Arc, thread};
+use zcash_proofs::circuit::sapling::TREE_DEPTH;
+
+fn make_tree(size: u64) -> NoteCommitmentTree<Bls12> {
+    let mut tree = NoteCommitmentTree::new(sapling_bls12::SAPLING.clone());
+    append_leaves(&mut tree, 0..size);
+    tree
+}
+
+fn append_leaves<S: TreeStore<Bls12>>(
+    tree: &mut NoteCommitmentTree<Bls12, S>,
+    positions: std::ops::Range<u64>,
+) {
+    for i in positions {
+        let position = tree
+            .append(&MerkleNoteHash::new(Fr::from(i + 1)))
+            .expect("should be able to append to tree");
+        assert_eq!(position, i);
+    }
+}
+
+fn hash_from_hex(hex: &str) -> Fr {
+    let bytes = hex_to_bytes(hex).expect("should be valid hex");
+    MerkleNoteHash::<Bls12>::read(&mut &bytes[..])
+        .expect("should be a valid hash")
+        .0
+}
+
+// Note commitments of the three notes in the genesis block
+// (ironfish/src/genesis/genesisBlock.ts), in the order they were appended to
+// the TypeScript tree.
+const GENESIS_LEAVES: [&str; 3] = [
+    "ff8c374c432fa5d8c593b43737310d4745b98a23427ba545beb65cb8621be245",
+    "3697d2a164eff65864c200478edfd93b20e0d09270d893bdb3ef6545eea9332c",
+    "53f9ee39d6891fbf38bbb867705ebcb295b3edfea98fa65fd3153f0420258a41",
+];
+
+fn temp_store_path() -> PathBuf {
+    env::temp_dir().join(format!("ironfish-tree-{:016x}", random::<u64>()))
+}
+
+#[test]
+fn test_empty_tree() {
+    let tree = make_tree(0);
+    assert_eq!(tree.size(), 0);
+    assert!(tree.root_hash().is_err());
+    assert!(tree.witness(0).is_err());
+    assert!(tree.leaf(0).unwrap().is_none());
+}
+
+#[test]
+fn test_single_leaf_root() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let tree = make_tree(1);
+
+    let mut expected = Fr::from(1);
+    for depth in 0..TREE_DEPTH {
+        expected = MerkleNoteHash::combine_hash(sapling, depth, &expected, &expected);
+    }
+    assert_eq!(tree.root_hash().unwrap(), expected);
+
+    let witness = tree.witness(0).unwrap();
+    assert_eq!(witness.root_hash, expected);
+    assert_eq!(witness.auth_path[0], WitnessNode::Left(Fr::from(1)));
+}
+
+/// Roots computed by the TypeScript tree in ironfish/src/merkletree for the
+/// genesis notes. The root at size 1 is the anchor of the spend in the
+/// genesis block, and the root at size 3 is the note commitment in its header.
+#[test]
+fn test_roots_match_typescript_tree() {
+    let mut tree = NoteCommitmentTree::new(sapling_bls12::SAPLING.clone());
+    for leaf in GENESIS_LEAVES.iter() {
+        tree.append(&MerkleNoteHash::new(hash_from_hex(leaf)))
+            .unwrap();
+    }
+
+    assert_eq!(
+        tree.past_root(1).unwrap(),
+        hash_from_hex("8f121c31b3f7de0774c46e4ee95cb03e5ffd291c2542d3591de4ffa841b64436")
+    );
+    assert_eq!(
+        tree.root_hash().unwrap(),
+        hash_from_hex("d3a4a5e2ff41a8dbb49f081fb75e6406d9360076d2297d70eb95b3e61d3ffe09")
+    );
+}
+
+/// In a tree with an odd number of leaves, a node without a right child is
+/// hashed with itself, as the TypeScript tree does.
+#[test]
+fn test_odd_tree_root() {
+    let sapling = &*sapling_bls12::SAPLING;
+    let leaves: Vec<Fr> = GENESIS_LEAVES
+        .iter()
+        .map(|leaf| hash_from_hex(leaf))
+        .collect();
+    let mut tree = NoteCommitmentTree::new(sapling_bls12::SAPLING.clone());
+    for leaf in leaves.iter() {
+        tree.append(&MerkleNoteHash::new(*leaf)).unwrap();
+    }
+
+    let left = MerkleNoteHash::combine_hash(sapling, 0, &leaves[0], &leaves[1]);
+    let right = MerkleNoteHash::combine_hash(sapling, 0, &leaves[2], &leaves[2]);
+    let mut expected = MerkleNoteHash::combine_hash(sapling, 1, &left, &right);
+    for depth in 2..TREE_DEPTH {
+        expected = MerkleNoteHash::combine_hash(sapling, depth, &expected, &expected);
+    }
+    assert_eq!(tree.root_hash().unwrap(), expected);
+
+    let witness = tree.witness(2).unwrap();
+    assert_eq!(witness.root_hash, expected);
+    assert_eq!(witness.auth_path[0], WitnessNode::Left(leaves[2]));
+    assert_eq!(witness.auth_path[1], WitnessNode::Right(left));
+    assert!(witness.verify(&MerkleNoteHash::new(leaves[2])));
+}
+
+#[test]
+fn test_witnesses_verify() {
+    let tree = make_tree(11);
+    let root_hash = tree.root_hash().unwrap();
+
+    for position in 0..tree.size() {
+        let witness = tree.witness(position).unwrap();
+        assert_eq!(witness.auth_path.len(), TREE_DEPTH);
+        assert_eq!(witness.tree_size(), 11);
+        assert_eq!(witness.root_hash(), root_hash);
+        assert_eq!(witness_position::<Bls12>(&witness), position);
+        assert!(witness.verify(&tree.leaf(position).unwrap().unwrap()));
+        assert!(!witness.verify(&MerkleNoteHash::new(Fr::from(100))));
+    }
+    assert!(tree.witness(11).is_err());
+}
+
+#[test]
+fn test_past_root_and_rewind() {
+    let mut tree = make_tree(9);
+
+    for size in 1..=9 {
+        let expected = make_tree(size).root_hash().unwrap();
+        assert_eq!(tree.past_root(size).unwrap(), expected);
+    }
+    assert!(tree.past_root(0).is_err());
+    assert!(tree.past_root(10).is_err());
+
+    let past_root = tree.past_root(5).unwrap();
+    tree.rewind(5).expect("should be able to rewind tree");
+    assert_eq!(tree.size(), 5);
+    assert_eq!(tree.root_hash().unwrap(), past_root);
+    assert!(tree.rewind(6).is_err());
+
+    tree.append(&MerkleNoteHash::new(Fr::from(6))).unwrap();
+    assert_eq!(tree.root_hash().unwrap(), make_tree(6).root_hash().unwrap());
+}
+
+#[test]
+fn test_memory_store_is_append_only() {
+    let mut store: MemoryTreeStore<Bls12> = MemoryTreeStore::new();
+    assert_eq!(store.leaf_count(), 0);
+    assert!(store.put_node(0, 1, Fr::from(1)).is_err());
+    store.put_node(0, 0, Fr::from(1)).unwrap();
+    store.put_node(0, 1, Fr::from(2)).unwrap();
+    store.put_node(1, 0, Fr::from(3)).unwrap();
+    assert_eq!(store.leaf_count(), 2);
+    assert_eq!(store.get_node(1, 0).unwrap(), Some(Fr::from(3)));
+    assert_eq!(store.get_node(2, 0).unwrap(), None);
+
+    store.truncate(1).unwrap();
+    assert_eq!(store.leaf_count(), 1);
+    assert_eq!(store.get_node(0, 1).unwrap(), None);
+    assert_eq!(store.get_node(1, 0).unwrap(), None);
+}
+
+#[test]
+fn test_file_store_persists_tree() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let path = temp_store_path();
+
+    {
+        let store = FileTreeStore::open(&path).expect("should be able to create store");
+        let mut tree = NoteCommitmentTree::with_store(sapling.clone(), store);
+        append_leaves(&mut tree, 0..7);
+        assert_eq!(tree.root_hash().unwrap(), make_tree(7).root_hash().unwrap());
+    }
+
+    let store = FileTreeStore::open(&path).expect("should be able to reopen store");
+    let mut tree = NoteCommitmentTree::with_store(sapling, store);
+    assert_eq!(tree.size(), 7);
+    assert_eq!(tree.root_hash().unwrap(), make_tree(7).root_hash().unwrap());
+
+    append_leaves(&mut tree, 7..10);
+    let memory_tree = make_tree(10);
+    assert_eq!(tree.root_hash().unwrap(), memory_tree.root_hash().unwrap());
+    assert_eq!(tree.witness(4).unwrap(), memory_tree.witness(4).unwrap());
+
+    tree.rewind(6).unwrap();
+    assert_eq!(tree.root_hash().unwrap(), make_tree(6).root_hash().unwrap());
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_file_store_recovers_interrupted_append() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let path = temp_store_path();
+
+    {
+        let store = FileTreeStore::open(&path).unwrap();
+        let mut tree = NoteCommitmentTree::with_store(sapling.clone(), store);
+        append_leaves(&mut tree, 0..3);
+    }
+    {
+        // Write the fourth leaf without the parent hashes it completes
+        let mut store: FileTreeStore<Bls12> = FileTreeStore::open(&path).unwrap();
+        store.put_node(0, 3, Fr::from(4)).unwrap();
+        assert_eq!(store.leaf_count(), 4);
+    }
+
+    let store = FileTreeStore::open(&path).unwrap();
+    assert_eq!(store.leaf_count(), 3);
+    let mut tree = NoteCommitmentTree::with_store(sapling, store);
+    assert_eq!(tree.root_hash().unwrap(), make_tree(3).root_hash().unwrap());
+    append_leaves(&mut tree, 3..4);
+    assert_eq!(tree.root_hash().unwrap(), make_tree(4).root_hash().unwrap());
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_file_store_concurrent_reads() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let path = temp_store_path();
+    {
+        let store = FileTreeStore::open(&path).unwrap();
+        let mut tree = NoteCommitmentTree::with_store(sapling, store);
+        append_leaves(&mut tree, 0..64);
+    }
+
+    let store: Arc<FileTreeStore<Bls12>> = Arc::new(FileTreeStore::open(&path).unwrap());
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let store = store.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    for i in 0..64 {
+                        assert_eq!(store.get_node(0, i).unwrap(), Some(Fr::from(i + 1)));
+                    }
+                }
+            })
+        })
+        .collect();
+    for reader in readers {
+        reader.join().expect("reads should all see the right nodes");
+    }
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_anchor_validation() {
+    let mut tree = make_tree(6);
+    let mut history = RootHistory::new(3);
+
+    for position in 0..6 {
+        let witness = tree.witness(position).unwrap();
+        assert!(tree
+            .is_valid_anchor(&witness.root_hash(), witness.tree_size())
+            .unwrap());
+        assert!(history
+            .is_valid_anchor(&tree, &witness.root_hash(), witness.tree_size())
+            .unwrap());
+    }
+    assert_eq!(history.len(), 1);
+
+    let past_root = tree.past_root(4).unwrap();
+    assert!(history.is_valid_anchor(&tree, &past_root, 4).unwrap());
+    assert!(!history.is_valid_anchor(&tree, &past_root, 5).unwrap());
+    assert!(!history.is_valid_anchor(&tree, &past_root, 0).unwrap());
+    assert!(!history.is_valid_anchor(&tree, &past_root, 7).unwrap());
+    assert!(!tree.is_valid_anchor(&past_root, 7).unwrap());
+    assert_eq!(history.len(), 3);
+
+    // The oldest size is evicted once the history is full
+    assert!(history
+        .is_valid_anchor(&tree, &tree.past_root(1).unwrap(), 1)
+        .unwrap());
+    assert_eq!(history.len(), 3);
+
+    // Roots above the rewound size change once new leaves are appended
+    tree.rewind(3).unwrap();
+    history.rewind(3);
+    tree.append(&MerkleNoteHash::new(Fr::from(100))).unwrap();
+    assert!(!history.is_valid_anchor(&tree, &past_root, 4).unwrap());
+    assert!(history
+        .is_valid_anchor(&tree, &tree.past_root(4).unwrap(), 4)
+        .unwrap());
+}
+
+#[test]
+fn test_incremental_witness_tracks_tree() {
+    let mut tree = make_tree(3);
+    let mut incremental = IncrementalWitness::from_tree(&tree, 2).unwrap();
+    let mut first = IncrementalWitness::from_tree(&tree, 0).unwrap();
+    assert_eq!(incremental.position(), 2);
+    assert_eq!(incremental.witness(), tree.witness(2).unwrap());
+
+    for i in 3..20 {
+        let hash = MerkleNoteHash::new(Fr::from(i + 1));
+        tree.append(&hash).unwrap();
+        incremental.append(&hash).unwrap();
+        first.append(&hash).unwrap();
+
+        assert_eq!(incremental.tree_size(), tree.size());
+        assert_eq!(incremental.root_hash(), tree.root_hash().unwrap());
+        assert_eq!(incremental.witness(), tree.witness(2).unwrap());
+        assert_eq!(first.witness(), tree.witness(0).unwrap());
+        assert!(incremental
+            .witness()
+            .verify(&tree.leaf(2).unwrap().unwrap()));
+    }
+}
+
+#[test]
+fn test_incremental_witness_from_tree() {
+    let tree = make_tree(13);
+    for position in 0..13 {
+        let incremental = IncrementalWitness::from_tree(&tree, position).unwrap();
+        assert_eq!(incremental.witness(), tree.witness(position).unwrap());
+    }
+    assert!(IncrementalWitness::from_tree(&tree, 13).is_err());
+}
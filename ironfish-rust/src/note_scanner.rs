@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::{keys::IncomingViewKey, merkle_note::MerkleNote, note::Note};
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+use zcash_primitives::jubjub::JubjubEngine;
+
+/// Finds the notes belonging to any of a set of accounts in a batch of
+/// encrypted notes.
+///
+/// Syncing a wallet means trying to decrypt every note on the chain with
+/// every account's incoming view key. Scanning a whole block's worth of notes
+/// for all the accounts in one call avoids crossing into Rust once per note
+/// per account. With the `native` feature, the notes are scanned in parallel.
+pub struct NoteScanner<J: JubjubEngine + pairing::MultiMillerLoop> {
+    view_keys: Vec<IncomingViewKey<J>>,
+}
+
+/// A note that was decrypted by one of the scanner's view keys.
+pub struct ScannedNote<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Position of the encrypted note in the batch passed to `scan`.
+    pub note_index: usize,
+
+    /// Position of the view key that decrypted the note, in the order the
+    /// keys were given to the scanner.
+    pub account: usize,
+
+    pub note: Note<J>,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> NoteScanner<J>
+where
+    J::Params: Send + Sync,
+{
+    /// Create a scanner for the accounts with the given incoming view keys.
+    pub fn new(view_keys: Vec<IncomingViewKey<J>>) -> Self {
+        NoteScanner { view_keys }
+    }
+
+    pub fn view_keys(&self) -> &[IncomingViewKey<J>] {
+        &self.view_keys
+    }
+
+    /// Trial decrypt each of the notes with every view key, and return the
+    /// notes that could be decrypted, in the order they appear in `notes`.
+    ///
+    /// A note is only returned once, for the first key that decrypts it, even
+    /// if several of the keys are the same.
+    pub fn scan(&self, notes: &[MerkleNote<J>]) -> Vec<ScannedNote<J>> {
+        #[cfg(feature = "native")]
+        let scanned = notes
+            .par_iter()
+            .enumerate()
+            .filter_map(|(note_index, note)| self.trial_decrypt(note_index, note))
+            .collect();
+
+        #[cfg(not(feature = "native"))]
+        let scanned = notes
+            .iter()
+            .enumerate()
+            .filter_map(|(note_index, note)| self.trial_decrypt(note_index, note))
+            .collect();
+
+        scanned
+    }
+
+    fn trial_decrypt(&self, note_index: usize, note: &MerkleNote<J>) -> Option<ScannedNote<J>> {
+        self.view_keys
+            .iter()
+            .enumerate()
+            .find_map(|(account, view_key)| {
+                note.decrypt_note_for_owner(view_key)
+                    .ok()
+                    .map(|note| ScannedNote {
+                        note_index,
+                        account,
+                        note,
+                    })
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NoteScanner;
+    use crate::{
+        keys::SaplingKey,
+        merkle_note::MerkleNote,
+        note::{Memo, Note},
+        sapling_bls12,
+    };
+    use pairing::bls12_381::Bls12;
+    use rand::{thread_rng, Rng};
+    use zcash_primitives::{
+        jubjub::{fs::Fs, ToUniform},
+        primitives::ValueCommitment,
+    };
+
+    #[test]
+    fn test_scan_notes() {
+        let sapling = &*sapling_bls12::SAPLING;
+        let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let keys: Vec<SaplingKey<Bls12>> = (0..3)
+            .map(|_| SaplingKey::generate_key(sapling.clone()))
+            .collect();
+        let stranger_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+
+        // Notes for accounts 2, nobody, 0, and 2 again
+        let owners = [&keys[2], &stranger_key, &keys[0], &keys[2]];
+        let notes: Vec<MerkleNote<Bls12>> = owners
+            .iter()
+            .enumerate()
+            .map(|(value, owner)| {
+                let note = Note::new(
+                    sapling.clone(),
+                    owner.generate_public_address(),
+                    value as u64,
+                    Memo::default(),
+                );
+                let diffie_hellman_keys = note.owner.generate_diffie_hellman_keys(&sapling.jubjub);
+                let mut buffer = [0u8; 64];
+                thread_rng().fill(&mut buffer[..]);
+                let value_commitment = ValueCommitment::<Bls12> {
+                    value: note.value,
+                    randomness: Fs::to_uniform(&buffer[..]),
+                };
+                MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys)
+            })
+            .collect();
+
+        let scanner = NoteScanner::new(
+            keys.iter()
+                .map(|key| key.incoming_view_key().clone())
+                .collect(),
+        );
+        let scanned = scanner.scan(&notes);
+
+        let found: Vec<(usize, usize, u64)> = scanned
+            .iter()
+            .map(|scanned| (scanned.note_index, scanned.account, scanned.note.value()))
+            .collect();
+        assert_eq!(found, vec![(0, 2, 0), (2, 0, 2), (3, 2, 3)]);
+
+        assert!(NoteScanner::new(vec![]).scan(&notes).is_empty());
+        assert!(scanner.scan(&[]).is_empty());
+    }
+}
@@ -3,7 +3,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use super::{
-    errors, is_small_order, keys::SaplingKey, merkle_note::MerkleNote, note::Note, Sapling,
+    errors, is_small_order,
+    keys::{PublicAddress, SaplingKey},
+    merkle_note::MerkleNote,
+    note::Note,
+    serializing::zeroize_scalar,
+    Sapling, SaplingVerifier,
 };
 use bellman::groth16;
 use ff::Field;
@@ -31,6 +36,14 @@ pub struct ReceiptParams<J: JubjubEngine + pairing::MultiMillerLoop> {
     /// Merkle note containing all the values verified by the proof. These values
     /// are shared on the blockchain and can be snapshotted into a Merkle Tree
     pub(crate) merkle_note: MerkleNote<J>,
+
+    /// Ephemeral secret key the note was encrypted with. Together with the
+    /// owner's address, it lets the sender decrypt the note again to disclose
+    /// the payment. See `PaymentDisclosure`.
+    pub(crate) ephemeral_secret_key: J::Fs,
+
+    /// Public address of the owner of the new note.
+    pub(crate) owner: PublicAddress<J>,
 }
 
 impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptParams<J> {
@@ -70,6 +83,8 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptParams<J> {
             proof,
             value_commitment_randomness,
             merkle_note,
+            ephemeral_secret_key: diffie_hellman_keys.0,
+            owner: note.owner.clone(),
         };
 
         Ok(receipt_proof)
@@ -104,6 +119,12 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptParams<J> {
     }
 }
 
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Drop for ReceiptParams<J> {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.ephemeral_secret_key);
+    }
+}
+
 /// The publicly visible values of a received note in a transaction. These
 /// values are calculated by the spender using only the public address of the
 /// owner of this new note.
@@ -123,11 +144,11 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptProof<J> {
     /// This is the main entry-point when reconstructing a serialized
     /// transaction.
     pub fn read<R: io::Read>(
-        sapling: Arc<Sapling<J>>,
+        jubjub: &J::Params,
         mut reader: R,
     ) -> Result<Self, errors::SaplingProofError> {
         let proof = groth16::Proof::read(&mut reader)?;
-        let merkle_note = MerkleNote::read(&mut reader, sapling)?;
+        let merkle_note = MerkleNote::read(&mut reader, jubjub)?;
 
         Ok(ReceiptProof { proof, merkle_note })
     }
@@ -139,12 +160,40 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptProof<J> {
 
     /// Verify that the proof demonstrates knowledge that a note exists with
     /// the value_commitment, public_key, and note_commitment on this proof.
-    pub fn verify_proof(&self, sapling: &Sapling<J>) -> Result<(), errors::SaplingProofError> {
-        if is_small_order(&sapling.jubjub, &self.merkle_note.value_commitment)
-            || is_small_order(&sapling.jubjub, &self.merkle_note.ephemeral_public_key)
+    pub fn verify_proof(
+        &self,
+        verifier: &SaplingVerifier<J>,
+    ) -> Result<(), errors::SaplingProofError> {
+        self.verify_not_small_order(&verifier.jubjub)?;
+
+        match groth16::verify_proof(
+            &verifier.receipt_verifying_key,
+            &self.proof,
+            &self.public_inputs()[..],
+        ) {
+            Ok(true) => Ok(()),
+            _ => Err(errors::SaplingProofError::VerificationFailed),
+        }
+    }
+
+    /// Confirm that the value commitment and ephemeral public key are not of
+    /// small order, which has to be checked outside the circuit.
+    pub(crate) fn verify_not_small_order(
+        &self,
+        jubjub: &J::Params,
+    ) -> Result<(), errors::SaplingProofError> {
+        if is_small_order(jubjub, &self.merkle_note.value_commitment)
+            || is_small_order(jubjub, &self.merkle_note.ephemeral_public_key)
         {
-            return Err(errors::SaplingProofError::VerificationFailed);
+            Err(errors::SaplingProofError::VerificationFailed)
+        } else {
+            Ok(())
         }
+    }
+
+    /// Convert the value_commitment, ephemeral public key and note commitment
+    /// to the public inputs of the output circuit.
+    pub(crate) fn public_inputs(&self) -> [J::Fr; 5] {
         let mut public_input = [J::Fr::zero(); 5];
         let (x, y) = self.merkle_note.value_commitment.to_xy();
         public_input[0] = x;
@@ -156,14 +205,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ReceiptProof<J> {
 
         public_input[4] = self.merkle_note.note_commitment;
 
-        match groth16::verify_proof(
-            &sapling.receipt_verifying_key,
-            &self.proof,
-            &public_input[..],
-        ) {
-            Ok(true) => Ok(()),
-            _ => Err(errors::SaplingProofError::VerificationFailed),
-        }
+        public_input
     }
     /// Get a MerkleNote, which can be used as a node in a Merkle Tree.
     pub fn merkle_note(&self) -> MerkleNote<J> {
@@ -201,7 +243,7 @@ mod test {
             sapling.clone(),
             spender_key.generate_public_address(),
             42,
-            Memo([0; 32]),
+            Memo::default(),
         );
 
         let receipt = ReceiptParams::new(sapling.clone(), &spender_key, &note)
@@ -219,7 +261,7 @@ mod test {
             .write(&mut serialized_proof)
             .expect("Should be able to serialize proof");
         let read_back_proof: ReceiptProof<Bls12> =
-            ReceiptProof::read(sapling.clone(), &mut serialized_proof[..].as_ref())
+            ReceiptProof::read(&sapling.jubjub, &mut serialized_proof[..].as_ref())
                 .expect("Should be able to deserialize valid proof");
 
         assert_eq!(proof.proof.a, read_back_proof.proof.a);
@@ -238,8 +280,8 @@ mod test {
             read_back_proof.merkle_note.ephemeral_public_key.to_xy()
         );
         assert_eq!(
-            proof.merkle_note.encrypted_note[..],
-            read_back_proof.merkle_note.encrypted_note[..]
+            proof.merkle_note.encrypted_note(),
+            read_back_proof.merkle_note.encrypted_note()
         );
         assert_eq!(
             proof.merkle_note.note_encryption_keys[..],
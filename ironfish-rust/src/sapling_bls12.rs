@@ -2,29 +2,41 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use super::errors::SaplingParamsError;
 pub use pairing::bls12_381::{Bls12, Fr};
+#[cfg(feature = "embedded-params")]
 use std::sync::Arc;
+use std::{io, path::Path};
 
 pub type Key = super::SaplingKey<Bls12>;
 pub type IncomingViewKey = super::IncomingViewKey<Bls12>;
 pub type OutgoingViewKey = super::OutgoingViewKey<Bls12>;
+pub type FullViewingKey = super::FullViewingKey<Bls12>;
 
 pub type PublicAddress = super::PublicAddress<Bls12>;
 pub type ViewKeys = super::ViewKeys<Bls12>;
 pub type Address = super::PublicAddress<Bls12>;
 pub type Sapling = super::Sapling<Bls12>;
+pub type SaplingVerifier = super::SaplingVerifier<Bls12>;
 pub type ProposedTransaction = super::ProposedTransaction<Bls12>;
 pub type ProposedSpend = super::SpendParams<Bls12>;
 pub type Transaction = super::Transaction<Bls12>;
 pub type ReceiptProof = super::ReceiptProof<Bls12>;
 pub type SimpleTransaction = super::SimpleTransaction<Bls12>;
+pub type PaymentDisclosure = super::PaymentDisclosure<Bls12>;
 pub type SpendProof = super::SpendProof<Bls12>;
 pub type Note = super::Note<Bls12>;
 pub type MerkleNote = super::MerkleNote<Bls12>;
+pub type CompactMerkleNote = super::CompactMerkleNote<Bls12>;
+pub type SentNoteInfo = super::SentNoteInfo<Bls12>;
 pub type MerkleNoteHash = super::MerkleNoteHash<Bls12>;
+pub type NoteCommitmentTree = super::NoteCommitmentTree<Bls12>;
+pub type NoteScanner = super::NoteScanner<Bls12>;
+pub type ScannedNote = super::ScannedNote<Bls12>;
 
 // Loads the Sapling object once when dereferenced,
 // then reuses the reference on future calls.
+#[cfg(feature = "embedded-params")]
 lazy_static! {
     pub static ref SAPLING: Arc<Sapling> = Arc::new(load());
 }
@@ -35,6 +47,33 @@ lazy_static! {
 ///
 /// Provided as a convenience method so clients don't have to depend
 /// explicitly on zcash_primitives just to define a JubjubBls12 point.
+#[cfg(feature = "embedded-params")]
 fn load() -> Sapling {
     Sapling::load(zcash_primitives::jubjub::JubjubBls12::new())
 }
+
+/// Load a sapling object configured to a BLS12 jubjub curve from the parameter
+/// files at the given paths.
+pub fn load_from_paths<P: AsRef<Path>, Q: AsRef<Path>>(
+    spend_path: P,
+    receipt_path: Q,
+) -> Result<Sapling, SaplingParamsError> {
+    Sapling::from_paths(
+        zcash_primitives::jubjub::JubjubBls12::new(),
+        spend_path,
+        receipt_path,
+    )
+}
+
+/// Load a sapling object configured to a BLS12 jubjub curve from the serialized
+/// spend and output parameters.
+pub fn load_from_readers<R: io::Read, S: io::Read>(
+    spend_reader: R,
+    receipt_reader: S,
+) -> Result<Sapling, SaplingParamsError> {
+    Sapling::from_readers(
+        zcash_primitives::jubjub::JubjubBls12::new(),
+        spend_reader,
+        receipt_reader,
+    )
+}
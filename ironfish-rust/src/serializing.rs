@@ -8,9 +8,12 @@
 /// all have functions for serializing, but their interface
 /// can be a bit clunky if you're just working with bytearrays.
 use super::errors;
-use ff::PrimeField;
+use ff::{Field, PrimeField};
 
-use std::io;
+use std::{
+    io, ptr,
+    sync::atomic::{self, Ordering},
+};
 use zcash_primitives::jubjub::{edwards, JubjubEngine, PrimeOrder};
 
 /// convert an edwards point of prime order to a bytes representation
@@ -30,6 +33,18 @@ pub(crate) fn scalar_to_bytes<F: PrimeField>(scalar: &F) -> [u8; 32] {
     result
 }
 
+/// Overwrite a secret scalar with zero, in a way that the compiler won't
+/// optimize away even though the value is never read again.
+///
+/// Field elements don't implement `Zeroize`, so this does for them what the
+/// zeroize crate does for byte arrays.
+pub(crate) fn zeroize_scalar<F: Field>(scalar: &mut F) {
+    // Safe because the reference guarantees the pointer is valid and aligned,
+    // and field elements are Copy, so nothing needs to be dropped.
+    unsafe { ptr::write_volatile(scalar, F::zero()) };
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
 #[allow(dead_code)]
 pub(crate) fn bytes_to_scalar<F: PrimeField>(bytes: &[u8; 32]) -> F {
     read_scalar(bytes[..].as_ref())
@@ -78,23 +93,51 @@ pub(crate) fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, ()> {
     Ok(bytes)
 }
 
+/// Read everything left in the reader, and return it along with its
+/// BLAKE2b-512 digest as a hexadecimal String.
+pub(crate) fn read_to_end_hashed<R: io::Read>(mut reader: R) -> io::Result<(Vec<u8>, String)> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let hash = bytes_to_hex(blake2b_simd::blake2b(&bytes).as_bytes());
+    Ok((bytes, hash))
+}
+
 pub(crate) mod aead {
     use crate::errors;
     use crypto::{
         aead::{AeadDecryptor, AeadEncryptor},
+        chacha20::ChaCha20,
         chacha20poly1305::ChaCha20Poly1305,
+        symmetriccipher::SynchronousStreamCipher,
     };
 
     pub const MAC_SIZE: usize = 16;
 
+    /// The associated data authenticated by `encrypt` and `decrypt`, which is
+    /// always eight zero bytes.
+    const DEFAULT_AAD: &[u8] = &[0; 8];
+
     /// Encrypt the plaintext using the given key, and append the MAC tag to the
     /// end of the output array to be decrypted and checked in one step below.
     ///
     /// This is just a facade around the ChaCha20Poly1305 struct. It ignores
     /// nonce and aad and automatically stores the mac tag.
     pub(crate) fn encrypt(key: &[u8], plaintext: &[u8], encrypted_output: &mut [u8]) {
+        encrypt_with_aad(key, DEFAULT_AAD, plaintext, encrypted_output)
+    }
+
+    /// Encrypt the plaintext like `encrypt`, but also authenticate the given
+    /// associated data, which has to be passed to `decrypt_with_aad` again.
+    ///
+    /// The nonce is still fixed, so each key must only be used once.
+    pub(crate) fn encrypt_with_aad(
+        key: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        encrypted_output: &mut [u8],
+    ) {
         assert_eq!(encrypted_output.len(), plaintext.len() + MAC_SIZE);
-        let mut encryptor = ChaCha20Poly1305::new(key, &[0; 8], &[0; 8]);
+        let mut encryptor = ChaCha20Poly1305::new(key, &[0; 8], aad);
         let mut tag = [0; MAC_SIZE];
         encryptor.encrypt(
             plaintext,
@@ -111,10 +154,21 @@ pub(crate) mod aead {
     pub(crate) fn decrypt(
         key: &[u8],
         ciphertext: &[u8],
+        plaintext_output: &mut [u8],
+    ) -> Result<(), errors::NoteError> {
+        decrypt_with_aad(key, DEFAULT_AAD, ciphertext, plaintext_output)
+    }
+
+    /// Decrypt text encrypted by `encrypt_with_aad`. Fails if the associated
+    /// data is not the same as it was when encrypting.
+    pub(crate) fn decrypt_with_aad(
+        key: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
         mut plaintext_output: &mut [u8],
     ) -> Result<(), errors::NoteError> {
         assert!(plaintext_output.len() == ciphertext.len() - MAC_SIZE);
-        let mut decryptor = ChaCha20Poly1305::new(key, &[0; 8], &[0; 8]);
+        let mut decryptor = ChaCha20Poly1305::new(key, &[0; 8], aad);
         let success = decryptor.decrypt(
             &ciphertext[..ciphertext.len() - MAC_SIZE],
             &mut plaintext_output,
@@ -128,9 +182,29 @@ pub(crate) mod aead {
         }
     }
 
+    /// Decrypt the start of a ciphertext created by `encrypt`, without the
+    /// rest of it or the mac tag.
+    ///
+    /// ChaCha20 is a stream cipher, so any prefix of the ciphertext can be
+    /// decrypted on its own. Nothing is authenticated, so the caller has to
+    /// check the plaintext some other way before trusting it.
+    pub(crate) fn decrypt_prefix(
+        key: &[u8],
+        ciphertext_prefix: &[u8],
+        plaintext_output: &mut [u8],
+    ) {
+        assert_eq!(plaintext_output.len(), ciphertext_prefix.len());
+        let mut cipher = ChaCha20::new(key, &[0; 8]);
+        // ChaCha20Poly1305 uses the first block of the key stream as the key
+        // for the mac, and encrypts with the blocks after it
+        let mut mac_key = [0; 64];
+        cipher.process(&[0; 64], &mut mac_key);
+        cipher.process(ciphertext_prefix, plaintext_output);
+    }
+
     #[cfg(test)]
     mod test {
-        use super::{decrypt, encrypt};
+        use super::{decrypt, decrypt_prefix, decrypt_with_aad, encrypt, encrypt_with_aad};
 
         #[test]
         fn test_aead_facade() {
@@ -143,6 +217,42 @@ pub(crate) mod aead {
             decrypt(&key[..], &encrypted_text[..], &mut decrypted_plaintext[..])
                 .expect("Should successfully decrypt with MAC verification");
             assert_eq!(&decrypted_plaintext, plaintext);
+
+            let mut decrypted_prefix = [0; 5];
+            decrypt_prefix(&key[..], &encrypted_text[..5], &mut decrypted_prefix[..]);
+            assert_eq!(&decrypted_prefix, b"hello");
+        }
+
+        #[test]
+        fn test_aead_associated_data() {
+            let key = b"I'm so secret!!!";
+            let plaintext = b"hello world";
+            let mut encrypted_text = [0; 27];
+            encrypt_with_aad(
+                &key[..],
+                b"context",
+                &plaintext[..],
+                &mut encrypted_text[..],
+            );
+
+            let mut decrypted_plaintext = [0; 11];
+            decrypt_with_aad(
+                &key[..],
+                b"context",
+                &encrypted_text[..],
+                &mut decrypted_plaintext[..],
+            )
+            .expect("Should successfully decrypt with the same associated data");
+            assert_eq!(&decrypted_plaintext, plaintext);
+
+            assert!(decrypt_with_aad(
+                &key[..],
+                b"another context",
+                &encrypted_text[..],
+                &mut decrypted_plaintext[..],
+            )
+            .is_err());
+            assert!(decrypt(&key[..], &encrypted_text[..], &mut decrypted_plaintext[..]).is_err());
         }
     }
 }
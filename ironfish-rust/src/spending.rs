@@ -11,7 +11,7 @@ use super::{
     nullifiers::Nullifier,
     serializing::read_scalar,
     witness::WitnessTrait,
-    Sapling,
+    Sapling, SaplingVerifier,
 };
 use bellman::gadgets::multipack;
 use bellman::groth16;
@@ -329,12 +329,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> SpendProof<J> {
         if is_small_order(jubjub, &self.randomized_public_key.0) {
             return Err(errors::SaplingProofError::VerificationFailed);
         }
-        let mut data_to_be_signed = [0; 64];
-        self.randomized_public_key
-            .0
-            .write(&mut data_to_be_signed[..32])
-            .expect("should be able to write public key point");
-        data_to_be_signed[32..].copy_from_slice(&signature_hash_value[..]);
+        let data_to_be_signed = self.signature_data(signature_hash_value);
 
         if !self.randomized_public_key.verify(
             &data_to_be_signed,
@@ -348,16 +343,55 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> SpendProof<J> {
         }
     }
 
+    /// The message that the authorizing signature signs: the randomized
+    /// public key followed by the transaction's signature hash.
+    pub(crate) fn signature_data(&self, signature_hash_value: &[u8; 32]) -> [u8; 64] {
+        let mut data_to_be_signed = [0; 64];
+        self.randomized_public_key
+            .0
+            .write(&mut data_to_be_signed[..32])
+            .expect("should be able to write public key point");
+        data_to_be_signed[32..].copy_from_slice(&signature_hash_value[..]);
+        data_to_be_signed
+    }
+
     /// Verify that the bellman proof confirms the randomized_public_key,
     /// commitment_value, nullifier, and anchor attached to this SpendProof.
     ///
     /// This entails converting all the values to appropriate inputs to the
     /// bellman circuit and executing it.
-    pub fn verify_proof(&self, sapling: &Sapling<J>) -> Result<(), errors::SaplingProofError> {
-        if is_small_order(&sapling.jubjub, &self.value_commitment) {
-            return Err(errors::SaplingProofError::VerificationFailed);
+    pub fn verify_proof(
+        &self,
+        verifier: &SaplingVerifier<J>,
+    ) -> Result<(), errors::SaplingProofError> {
+        self.verify_not_small_order(&verifier.jubjub)?;
+
+        match groth16::verify_proof(
+            &verifier.spend_verifying_key,
+            &self.proof,
+            &self.public_inputs()[..],
+        ) {
+            Ok(true) => Ok(()),
+            _ => Err(errors::SaplingProofError::VerificationFailed),
         }
+    }
 
+    /// Confirm that the value commitment is not of small order, which has to
+    /// be checked outside the circuit.
+    pub(crate) fn verify_not_small_order(
+        &self,
+        jubjub: &J::Params,
+    ) -> Result<(), errors::SaplingProofError> {
+        if is_small_order(jubjub, &self.value_commitment) {
+            Err(errors::SaplingProofError::VerificationFailed)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Convert the randomized_public_key, value_commitment, anchor and
+    /// nullifier to the public inputs of the spend circuit.
+    pub(crate) fn public_inputs(&self) -> [J::Fr; 7] {
         let mut public_input = [J::Fr::zero(); 7];
         let (x, y) = self.randomized_public_key.0.to_xy();
         public_input[0] = x;
@@ -374,10 +408,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> SpendProof<J> {
         public_input[5] = nullifier[0];
         public_input[6] = nullifier[1];
 
-        match groth16::verify_proof(&sapling.spend_verifying_key, &self.proof, &public_input[..]) {
-            Ok(true) => Ok(()),
-            _ => Err(errors::SaplingProofError::VerificationFailed),
-        }
+        public_input
     }
 
     /// Serialize the fields that are needed in calculating a signature to
@@ -423,9 +454,9 @@ mod test {
     extern crate bellman;
     extern crate pairing;
 
-    use super::{SpendParams, SpendProof};
+    use super::{witness_position, SpendParams, SpendProof};
     use crate::{
-        keys::SaplingKey,
+        keys::{FullViewingKey, SaplingKey},
         note::{Memo, Note},
         sapling_bls12,
         test_util::make_fake_witness,
@@ -440,6 +471,7 @@ mod test {
 
         let key = SaplingKey::generate_key(sapling.clone());
         let public_address = key.generate_public_address();
+        let view_key = key.full_viewing_key().hex_key();
 
         let note_randomness = random();
 
@@ -447,7 +479,7 @@ mod test {
             sapling.clone(),
             public_address.clone(),
             note_randomness,
-            Memo([0; 32]),
+            Memo::default(),
         );
         let witness = make_fake_witness(sapling.clone(), &note);
 
@@ -462,6 +494,14 @@ mod test {
         proof
             .verify_proof(&sapling)
             .expect("proof should check out");
+
+        // a watch-only wallet can recognize the spend without the spending key
+        let watch_only = FullViewingKey::from_hex(sapling.clone(), &view_key)
+            .expect("should be able to load view key");
+        assert_eq!(
+            note.nullifier_from_view_key(&watch_only, witness_position::<Bls12>(&witness)),
+            proof.nullifier()
+        );
         proof
             .verify_signature(&sapling.jubjub, &sig_hash)
             .expect("should be able to verify signature");
@@ -0,0 +1,283 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::Transaction;
+use crate::{errors::BatchVerificationError, is_small_order, SaplingVerifier};
+use bellman::groth16;
+use ff::Field;
+use pairing::MillerLoopResult;
+use rand::rngs::OsRng;
+use std::ops::{AddAssign, MulAssign, SubAssign};
+use zcash_primitives::jubjub::{FixedGenerators, JubjubEngine};
+use zcash_primitives::redjubjub::{self, BatchEntry};
+
+/// Verifies the proofs and signatures of many transactions at once, such as
+/// all the transactions in a block.
+///
+/// Checking a single Groth16 proof takes a multi-Miller loop over three
+/// pairings followed by a final exponentiation. The batch instead scales each
+/// proof's verification equation by a random scalar and multiplies them
+/// together, so all the proofs made with the same parameters are checked with
+/// a single multi-Miller loop and two final exponentiations. If any proof is
+/// invalid, the combined equation fails except with negligible probability.
+///
+/// The spend authorizing signatures and the binding signatures are batched
+/// as well, with one RedJubjub batch for each of the two generators they are
+/// made with.
+///
+/// When a batch fails, each item in it is verified individually to find the
+/// first one that is invalid. If every item passes on its own, the batch is
+/// still rejected.
+pub struct BatchVerifier<'a, J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// Verifying keys for the spend and receipt circuits, and the jubjub
+    /// parameters for the signatures.
+    verifier: &'a SaplingVerifier<J>,
+
+    /// Transactions to verify, in the order they were queued.
+    transactions: Vec<&'a Transaction<J>>,
+}
+
+impl<'a, J: JubjubEngine + pairing::MultiMillerLoop> BatchVerifier<'a, J> {
+    pub fn new(verifier: &'a SaplingVerifier<J>) -> Self {
+        BatchVerifier {
+            verifier,
+            transactions: vec![],
+        }
+    }
+
+    /// Add a transaction to the batch. Errors identify the transaction by
+    /// the order in which it was queued.
+    pub fn queue(&mut self, transaction: &'a Transaction<J>) {
+        self.transactions.push(transaction);
+    }
+
+    /// Number of transactions in the batch.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Verify all the proofs and signatures in the batch. This accepts
+    /// exactly the transactions that `Transaction::verify` accepts.
+    pub fn verify(&self) -> Result<(), BatchVerificationError> {
+        self.verify_proofs()?;
+        self.verify_signatures()
+    }
+
+    /// Verify all the spend and receipt proofs in the batch. On failure,
+    /// returns which proof in which transaction was invalid.
+    pub fn verify_proofs(&self) -> Result<(), BatchVerificationError> {
+        let mut spends = vec![];
+        let mut receipts = vec![];
+
+        for (transaction_index, transaction) in self.transactions.iter().enumerate() {
+            for (spend_index, spend) in transaction.spends.iter().enumerate() {
+                spend
+                    .verify_not_small_order(&self.verifier.jubjub)
+                    .map_err(|_| BatchVerificationError::InvalidSpendProof {
+                        transaction: transaction_index,
+                        spend: spend_index,
+                    })?;
+                spends.push((&spend.proof, spend.public_inputs().to_vec()));
+            }
+            for (receipt_index, receipt) in transaction.receipts.iter().enumerate() {
+                receipt
+                    .verify_not_small_order(&self.verifier.jubjub)
+                    .map_err(|_| BatchVerificationError::InvalidReceiptProof {
+                        transaction: transaction_index,
+                        receipt: receipt_index,
+                    })?;
+                receipts.push((&receipt.proof, receipt.public_inputs().to_vec()));
+            }
+        }
+
+        if verify_groth16_batch(&self.verifier.spend_vk, &spends)
+            && verify_groth16_batch(&self.verifier.receipt_vk, &receipts)
+        {
+            return Ok(());
+        }
+
+        // At least one proof is invalid; check them one at a time to find it.
+        for (transaction_index, transaction) in self.transactions.iter().enumerate() {
+            for (spend_index, spend) in transaction.spends.iter().enumerate() {
+                spend.verify_proof(self.verifier).map_err(|_| {
+                    BatchVerificationError::InvalidSpendProof {
+                        transaction: transaction_index,
+                        spend: spend_index,
+                    }
+                })?;
+            }
+            for (receipt_index, receipt) in transaction.receipts.iter().enumerate() {
+                receipt.verify_proof(self.verifier).map_err(|_| {
+                    BatchVerificationError::InvalidReceiptProof {
+                        transaction: transaction_index,
+                        receipt: receipt_index,
+                    }
+                })?;
+            }
+        }
+
+        Err(BatchVerificationError::InvalidProofBatch)
+    }
+
+    /// Verify the authorizing signature of every spend and the binding
+    /// signature of every transaction in the batch. On failure, returns which
+    /// signature was invalid.
+    pub fn verify_signatures(&self) -> Result<(), BatchVerificationError> {
+        let jubjub = &self.verifier.jubjub;
+
+        // The messages are collected first so the batch entries can borrow
+        // them.
+        let mut spend_messages = vec![];
+        let mut binding_messages = vec![];
+        for (transaction_index, transaction) in self.transactions.iter().enumerate() {
+            let signature_hash = transaction.transaction_signature_hash();
+            for (spend_index, spend) in transaction.spends.iter().enumerate() {
+                if is_small_order(jubjub, &spend.randomized_public_key.0) {
+                    return Err(BatchVerificationError::InvalidSpendSignature {
+                        transaction: transaction_index,
+                        spend: spend_index,
+                    });
+                }
+                spend_messages.push((
+                    spend.randomized_public_key.clone(),
+                    spend.signature_data(&signature_hash),
+                    spend.authorizing_signature,
+                ));
+            }
+
+            let (public_key, message) = transaction
+                .binding_signature_data(self.verifier, &transaction.binding_verification_key())
+                .map_err(|_| BatchVerificationError::InvalidBindingSignature {
+                    transaction: transaction_index,
+                })?;
+            binding_messages.push((public_key, message, transaction.binding_signature));
+        }
+
+        if verify_signature_batch(
+            &spend_messages,
+            FixedGenerators::SpendingKeyGenerator,
+            jubjub,
+        ) && verify_signature_batch(
+            &binding_messages,
+            FixedGenerators::ValueCommitmentRandomness,
+            jubjub,
+        ) {
+            return Ok(());
+        }
+
+        // At least one signature is invalid; check them one at a time to find
+        // it.
+        for (transaction_index, transaction) in self.transactions.iter().enumerate() {
+            let signature_hash = transaction.transaction_signature_hash();
+            for (spend_index, spend) in transaction.spends.iter().enumerate() {
+                spend
+                    .verify_signature(jubjub, &signature_hash)
+                    .map_err(|_| BatchVerificationError::InvalidSpendSignature {
+                        transaction: transaction_index,
+                        spend: spend_index,
+                    })?;
+            }
+            transaction
+                .verify_binding_signature(self.verifier, &transaction.binding_verification_key())
+                .map_err(|_| BatchVerificationError::InvalidBindingSignature {
+                    transaction: transaction_index,
+                })?;
+        }
+
+        Err(BatchVerificationError::InvalidSignatureBatch)
+    }
+}
+
+/// Check a batch of RedJubjub signatures made with the same generator, given
+/// as the public key, signed message and signature of each.
+fn verify_signature_batch<J: JubjubEngine + pairing::MultiMillerLoop>(
+    signatures: &[(redjubjub::PublicKey<J>, [u8; 64], redjubjub::Signature)],
+    generator: FixedGenerators,
+    jubjub: &J::Params,
+) -> bool {
+    let batch: Vec<BatchEntry<J>> = signatures
+        .iter()
+        .map(|(public_key, message, signature)| BatchEntry {
+            vk: public_key.clone(),
+            msg: &message[..],
+            sig: *signature,
+        })
+        .collect();
+
+    redjubjub::batch_verify(&mut OsRng, &batch[..], generator, jubjub)
+}
+
+/// Check a batch of Groth16 proofs that were all made with the same verifying
+/// key.
+///
+/// Each proof satisfies
+///     e(A, B) = e(alpha, beta) * e(IC, gamma) * e(C, delta)
+/// where IC is the combination of the verifying key's `ic` points with the
+/// proof's public inputs. Raising the equation for proof j to a random r_j and
+/// multiplying them all gives
+///     prod e(r_j A_j, B_j) * e(-sum r_j IC_j, gamma) * e(-sum r_j C_j, delta)
+///         = e((sum r_j) alpha, beta)
+/// The left side is computed in one multi-Miller loop. The sum over IC_j is
+/// taken per input, so it only adds one term for each point in `ic`.
+fn verify_groth16_batch<J: JubjubEngine + pairing::MultiMillerLoop>(
+    verifying_key: &groth16::VerifyingKey<J>,
+    batch: &[(&groth16::Proof<J>, Vec<J::Fr>)],
+) -> bool {
+    if batch.is_empty() {
+        return true;
+    }
+
+    let gamma = J::G2Prepared::from(verifying_key.gamma_g2);
+    let delta = J::G2Prepared::from(verifying_key.delta_g2);
+    let proof_b: Vec<J::G2Prepared> = batch
+        .iter()
+        .map(|(proof, _)| J::G2Prepared::from(proof.b))
+        .collect();
+
+    // Randomized sum of the public inputs for each point in `ic`, where the
+    // first point is multiplied by a constant input of one.
+    let mut input_sums = vec![J::Fr::zero(); verifying_key.ic.len()];
+    let mut terms = Vec::with_capacity(batch.len() * 2 + input_sums.len());
+
+    for ((proof, public_inputs), b) in batch.iter().zip(proof_b.iter()) {
+        if public_inputs.len() + 1 != verifying_key.ic.len() {
+            return false;
+        }
+
+        let randomizer = J::Fr::random(&mut OsRng);
+        let mut negated_randomizer = J::Fr::zero();
+        negated_randomizer.sub_assign(&randomizer);
+
+        input_sums[0].add_assign(&randomizer);
+        for (sum, input) in input_sums[1..].iter_mut().zip(public_inputs.iter()) {
+            let mut term = *input;
+            term.mul_assign(&randomizer);
+            sum.add_assign(&term);
+        }
+
+        terms.push((J::G1Affine::from(proof.a * randomizer), b));
+        terms.push((J::G1Affine::from(proof.c * negated_randomizer), &delta));
+    }
+
+    let randomizer_sum = input_sums[0];
+    for (sum, ic) in input_sums.iter().zip(verifying_key.ic.iter()) {
+        let mut negated_sum = J::Fr::zero();
+        negated_sum.sub_assign(sum);
+        terms.push((J::G1Affine::from(*ic * negated_sum), &gamma));
+    }
+
+    let term_refs: Vec<(&J::G1Affine, &J::G2Prepared)> =
+        terms.iter().map(|(g1, g2)| (g1, *g2)).collect();
+    let left = J::multi_miller_loop(&term_refs[..]).final_exponentiation();
+    let right = J::pairing(
+        &J::G1Affine::from(verifying_key.alpha_g1 * randomizer_sum),
+        &verifying_key.beta_g2,
+    );
+
+    left == right
+}
@@ -0,0 +1,413 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Payment disclosures, which prove to a third party such as an auditor that
+//! a transaction paid a specific note to a specific address, without handing
+//! over the outgoing view key that would reveal every other note the sender
+//! created.
+//!
+//! A disclosure reveals the ephemeral secret key of a single receipt. With it
+//! and the recipient's address, anyone can recompute the shared secret the
+//! note was encrypted with, and nothing else. The disclosure is signed with the
+//! transaction's binding signature key, which only the creator of the
+//! transaction knows, and whose public key anyone can recompute from the
+//! posted transaction. It is laid out as:
+//!
+//!  *  transaction signature hash (32 bytes)
+//!  *  receipt index (8 bytes, little endian)
+//!  *  ephemeral secret key (32 bytes)
+//!  *  recipient public address (43 bytes)
+//!  *  message length (2 bytes, little endian) and UTF-8 message
+//!  *  signature (64 bytes)
+
+use super::{ProposedTransaction, Transaction};
+use crate::{
+    errors::PaymentDisclosureError,
+    keys::{shared_secret, PublicAddress},
+    note::Note,
+    serializing::{read_scalar, scalar_to_bytes},
+    SaplingVerifier,
+};
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rand::rngs::OsRng;
+use std::{convert::TryFrom, io, sync::Arc};
+use zcash_primitives::jubjub::{FixedGenerators, JubjubEngine};
+use zcash_primitives::redjubjub::{PrivateKey, PublicKey, Signature};
+
+const DISCLOSURE_HASH_PERSONALIZATION: &[u8; 8] = b"Bnpaydis";
+
+/// A signed statement from the creator of a transaction that one of its
+/// receipts paid a note to the given address, with a message for whoever
+/// checks it.
+pub struct PaymentDisclosure<J: JubjubEngine + pairing::MultiMillerLoop> {
+    verifier: Arc<SaplingVerifier<J>>,
+
+    /// Signature hash of the transaction the receipt is in
+    transaction_hash: [u8; 32],
+
+    /// Position of the disclosed receipt in the transaction
+    receipt_index: u64,
+
+    /// Ephemeral secret key the receipt's note was encrypted with
+    ephemeral_secret_key: J::Fs,
+
+    /// Address the note was paid to
+    recipient: PublicAddress<J>,
+
+    /// Free form message from the sender, such as an invoice number
+    message: String,
+
+    /// Signature over all of the above with the transaction's binding
+    /// signature key
+    signature: Signature,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
+    /// Disclose the note created by the receipt at the given index, so that
+    /// anyone can verify the payment against the posted transaction.
+    ///
+    /// This has to be called after the transaction has been posted. Posting
+    /// can add a change receipt, which changes the transaction the disclosure
+    /// is signed for, so it fails with `NotPosted` before then.
+    pub fn disclose_payment(
+        &self,
+        receipt_index: usize,
+        message: &str,
+    ) -> Result<PaymentDisclosure<J>, PaymentDisclosureError> {
+        if !self.posted {
+            return Err(PaymentDisclosureError::NotPosted);
+        }
+        let receipt = self
+            .receipts
+            .get(receipt_index)
+            .ok_or(PaymentDisclosureError::InvalidReceiptIndex)?;
+        if u16::try_from(message.len()).is_err() {
+            return Err(PaymentDisclosureError::InvalidMessage);
+        }
+
+        let transaction_hash = self.transaction_signature_hash();
+        let receipt_index = receipt_index as u64;
+        let disclosure_hash = disclosure_hash(
+            &transaction_hash,
+            receipt_index,
+            &receipt.ephemeral_secret_key,
+            &receipt.owner,
+            message,
+        );
+
+        let private_key = PrivateKey::<J>(self.binding_signature_key);
+        let public_key = PublicKey::from_private(
+            &private_key,
+            FixedGenerators::ValueCommitmentRandomness,
+            &self.sapling.jubjub,
+        );
+        let signature = private_key.sign(
+            &data_to_sign(&public_key, &disclosure_hash),
+            &mut OsRng,
+            FixedGenerators::ValueCommitmentRandomness,
+            &self.sapling.jubjub,
+        );
+
+        Ok(PaymentDisclosure {
+            verifier: self.sapling.verifier().clone(),
+            transaction_hash,
+            receipt_index,
+            ephemeral_secret_key: receipt.ephemeral_secret_key,
+            recipient: receipt.owner.clone(),
+            message: message.to_string(),
+            signature,
+        })
+    }
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> PaymentDisclosure<J> {
+    /// Load a PaymentDisclosure from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(
+        verifier: Arc<SaplingVerifier<J>>,
+        mut reader: R,
+    ) -> Result<Self, PaymentDisclosureError> {
+        let mut transaction_hash = [0; 32];
+        reader.read_exact(&mut transaction_hash)?;
+        let receipt_index = reader.read_u64::<LittleEndian>()?;
+        let ephemeral_secret_key = read_scalar(&mut reader)?;
+        let recipient = PublicAddress::read(&verifier.jubjub, &mut reader)?;
+
+        let message_length = reader.read_u16::<LittleEndian>()?;
+        let mut message_bytes = vec![0; message_length as usize];
+        reader.read_exact(&mut message_bytes)?;
+        let message =
+            String::from_utf8(message_bytes).map_err(|_| PaymentDisclosureError::InvalidMessage)?;
+
+        let signature = Signature::read(&mut reader)?;
+
+        Ok(PaymentDisclosure {
+            verifier,
+            transaction_hash,
+            receipt_index,
+            ephemeral_secret_key,
+            recipient,
+            message,
+            signature,
+        })
+    }
+
+    /// Stow the bytes of this PaymentDisclosure in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.transaction_hash)?;
+        writer.write_u64::<LittleEndian>(self.receipt_index)?;
+        writer.write_all(&scalar_to_bytes(&self.ephemeral_secret_key))?;
+        self.recipient.write(&mut writer)?;
+        writer.write_u16::<LittleEndian>(self.message.len() as u16)?;
+        writer.write_all(self.message.as_bytes())?;
+        self.signature.write(&mut writer)?;
+        Ok(())
+    }
+
+    /// Check the disclosure against the transaction it was made for, and
+    /// return the disclosed note. Confirms that:
+    ///  *  the disclosure is for this transaction and one of its receipts
+    ///  *  it was signed by the creator of the transaction
+    ///  *  the ephemeral secret key is the one the receipt was made with
+    ///  *  the receipt's note decrypts with it, is owned by the recipient,
+    ///     and matches the receipt's note commitment
+    ///
+    /// This doesn't verify the transaction itself.
+    pub fn verify(&self, transaction: &Transaction<J>) -> Result<Note<J>, PaymentDisclosureError> {
+        if transaction.transaction_signature_hash() != self.transaction_hash {
+            return Err(PaymentDisclosureError::InvalidTransaction);
+        }
+        let merkle_note = &transaction
+            .receipts
+            .get(
+                usize::try_from(self.receipt_index)
+                    .map_err(|_| PaymentDisclosureError::InvalidReceiptIndex)?,
+            )
+            .ok_or(PaymentDisclosureError::InvalidReceiptIndex)?
+            .merkle_note;
+
+        let jubjub = &self.verifier.jubjub;
+        let (public_key, _) = transaction
+            .binding_signature_data(&self.verifier, &transaction.binding_verification_key())
+            .map_err(|_| PaymentDisclosureError::InvalidSignature)?;
+        let disclosure_hash = disclosure_hash(
+            &self.transaction_hash,
+            self.receipt_index,
+            &self.ephemeral_secret_key,
+            &self.recipient,
+            &self.message,
+        );
+        if !public_key.verify(
+            &data_to_sign(&public_key, &disclosure_hash),
+            &self.signature,
+            FixedGenerators::ValueCommitmentRandomness,
+            jubjub,
+        ) {
+            return Err(PaymentDisclosureError::InvalidSignature);
+        }
+
+        if self
+            .recipient
+            .diversifier_point
+            .mul(self.ephemeral_secret_key, jubjub)
+            != merkle_note.ephemeral_public_key
+        {
+            return Err(PaymentDisclosureError::InvalidEphemeralKey);
+        }
+
+        let shared_key = shared_secret(
+            jubjub,
+            &self.ephemeral_secret_key,
+            &self.recipient.transmission_key,
+            &merkle_note.ephemeral_public_key,
+        );
+        let note = Note::from_spender_encrypted(
+            self.verifier.clone(),
+            self.recipient.transmission_key.clone(),
+            &shared_key,
+            &merkle_note.ephemeral_public_key,
+            &merkle_note.note_commitment,
+            merkle_note.encrypted_note(),
+        )?;
+        if note.owner != self.recipient {
+            return Err(PaymentDisclosureError::InvalidRecipient);
+        }
+        note.verify_commitment(merkle_note.note_commitment)?;
+
+        Ok(note)
+    }
+
+    /// Signature hash of the transaction the disclosure was made for.
+    pub fn transaction_hash(&self) -> [u8; 32] {
+        self.transaction_hash
+    }
+
+    pub fn receipt_index(&self) -> u64 {
+        self.receipt_index
+    }
+
+    pub fn recipient(&self) -> PublicAddress<J> {
+        self.recipient.clone()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Hash the disclosed fields, so the signature covers all of them.
+fn disclosure_hash<J: JubjubEngine + pairing::MultiMillerLoop>(
+    transaction_hash: &[u8; 32],
+    receipt_index: u64,
+    ephemeral_secret_key: &J::Fs,
+    recipient: &PublicAddress<J>,
+    message: &str,
+) -> [u8; 32] {
+    let mut hasher = Blake2b::new()
+        .hash_length(32)
+        .personal(DISCLOSURE_HASH_PERSONALIZATION)
+        .to_state();
+
+    hasher.update(transaction_hash);
+    hasher.write_u64::<LittleEndian>(receipt_index).unwrap();
+    hasher.update(&scalar_to_bytes(ephemeral_secret_key));
+    hasher.update(&recipient.public_address());
+    hasher
+        .write_u16::<LittleEndian>(message.len() as u16)
+        .unwrap();
+    hasher.update(message.as_bytes());
+
+    let mut hash_result = [0; 32];
+    hash_result[..].clone_from_slice(&hasher.finalize().as_ref());
+    hash_result
+}
+
+/// Prefix the hash with the public key it is signed with, the same way the
+/// binding signature is. The hash is personalized differently from the
+/// transaction signature hash, so a disclosure signature can't be passed off
+/// as a binding signature or the other way around.
+fn data_to_sign<J: JubjubEngine + pairing::MultiMillerLoop>(
+    public_key: &PublicKey<J>,
+    disclosure_hash: &[u8; 32],
+) -> [u8; 64] {
+    let mut data_to_sign = [0; 64];
+    public_key
+        .0
+        .write(&mut data_to_sign[..32])
+        .expect("Should be able to copy key");
+    (&mut data_to_sign[32..]).copy_from_slice(disclosure_hash);
+    data_to_sign
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        errors::PaymentDisclosureError,
+        keys::SaplingKey,
+        note::{Memo, Note},
+        sapling_bls12,
+        test_util::make_fake_witness,
+        transaction::{PaymentDisclosure, ProposedTransaction},
+    };
+    use pairing::bls12_381::Bls12;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_payment_disclosure() {
+        let sapling = sapling_bls12::SAPLING.clone();
+        let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+        let in_note = Note::new(
+            sapling.clone(),
+            spender_key.generate_public_address(),
+            42,
+            Memo::try_from("invoice 17").unwrap(),
+        );
+        let out_note = Note::new(
+            sapling.clone(),
+            receiver_key.generate_public_address(),
+            40,
+            Memo::try_from("invoice 17").unwrap(),
+        );
+        let witness = make_fake_witness(sapling.clone(), &in_note);
+
+        let mut transaction = ProposedTransaction::new(sapling.clone());
+        transaction
+            .spend(spender_key.clone(), &in_note, &witness)
+            .expect("should be able to prove spend");
+        transaction
+            .receive(&spender_key, &out_note)
+            .expect("should be able to prove receipt");
+        assert!(matches!(
+            transaction.disclose_payment(0, "paid in full"),
+            Err(PaymentDisclosureError::NotPosted)
+        ));
+        let public_transaction = transaction
+            .post(&spender_key, None, 1)
+            .expect("should be able to post transaction");
+
+        let disclosure = transaction
+            .disclose_payment(0, "paid in full")
+            .expect("should be able to disclose payment");
+        let mut serialized = vec![];
+        disclosure
+            .write(&mut serialized)
+            .expect("should be able to serialize disclosure");
+        let disclosure = PaymentDisclosure::read(sapling.verifier().clone(), &serialized[..])
+            .expect("should be able to deserialize disclosure");
+        assert_eq!(disclosure.message(), "paid in full");
+        assert_eq!(disclosure.receipt_index(), 0);
+        assert_eq!(
+            disclosure.transaction_hash(),
+            public_transaction.transaction_signature_hash()
+        );
+
+        let note = disclosure
+            .verify(&public_transaction)
+            .expect("disclosure should verify");
+        assert_eq!(note.value(), 40);
+        assert!(note.owner() == receiver_key.generate_public_address());
+        assert_eq!(note.memo(), Memo::try_from("invoice 17").unwrap());
+        assert!(disclosure.recipient() == note.owner());
+
+        // The change receipt can be disclosed too
+        let change = transaction
+            .disclose_payment(1, "")
+            .expect("should be able to disclose change")
+            .verify(&public_transaction)
+            .expect("change disclosure should verify");
+        assert_eq!(change.value(), 1);
+
+        assert!(matches!(
+            transaction.disclose_payment(2, ""),
+            Err(PaymentDisclosureError::InvalidReceiptIndex)
+        ));
+
+        // Changing the message invalidates the signature
+        let message_offset = 32 + 8 + 32 + 43 + 2;
+        serialized[message_offset] ^= 1;
+        let tampered = PaymentDisclosure::read(sapling.verifier().clone(), &serialized[..])
+            .expect("should still be able to deserialize disclosure");
+        assert!(matches!(
+            tampered.verify(&public_transaction),
+            Err(PaymentDisclosureError::InvalidSignature)
+        ));
+
+        // A disclosure only verifies against its own transaction
+        let mut other_transaction = ProposedTransaction::new(sapling.clone());
+        other_transaction
+            .spend(spender_key.clone(), &in_note, &witness)
+            .expect("should be able to prove spend");
+        other_transaction
+            .receive(&spender_key, &out_note)
+            .expect("should be able to prove receipt");
+        let other_public_transaction = other_transaction
+            .post(&spender_key, None, 1)
+            .expect("should be able to post transaction");
+        assert!(matches!(
+            disclosure.verify(&other_public_transaction),
+            Err(PaymentDisclosureError::InvalidTransaction)
+        ));
+    }
+}
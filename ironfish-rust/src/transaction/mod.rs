@@ -10,7 +10,7 @@ use super::{
     receiving::{ReceiptParams, ReceiptProof},
     spending::{SpendParams, SpendProof},
     witness::WitnessTrait,
-    Sapling,
+    Sapling, SaplingVerifier,
 };
 use blake2b_simd::Params as Blake2b;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
@@ -22,6 +22,14 @@ use zcash_primitives::redjubjub::{PrivateKey, PublicKey, Signature};
 use std::{io, slice::Iter, sync::Arc};
 use zcash_primitives::jubjub::{edwards, FixedGenerators, JubjubEngine, JubjubParams, Unknown};
 
+mod batch;
+pub use batch::BatchVerifier;
+mod disclosure;
+pub use disclosure::PaymentDisclosure;
+#[cfg(feature = "native")]
+mod parallel;
+#[cfg(feature = "native")]
+pub use parallel::ParallelTransactionBuilder;
 mod simple;
 pub use simple::SimpleTransaction;
 use std::ops::AddAssign;
@@ -71,6 +79,10 @@ pub struct ProposedTransaction<J: JubjubEngine + pairing::MultiMillerLoop> {
     /// removed from the mempool. A value of 0 indicates the transaction will
     /// not expire.
     expiration_sequence: u32,
+
+    /// Whether the transaction has been posted. Payments can only be
+    /// disclosed once the receipts are final.
+    posted: bool,
     //
     // NOTE: If adding fields here, you may need to add fields to
     // signature hash method, and also to Transaction.
@@ -86,6 +98,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
             receipts: vec![],
             transaction_fee: 0,
             expiration_sequence: 0,
+            posted: false,
         }
     }
 
@@ -121,16 +134,23 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
         note: &Note<J>,
     ) -> Result<(), SaplingProofError> {
         let proof = ReceiptParams::new(self.sapling.clone(), spender_key, note)?;
-
-        self.increment_binding_signature_key(&proof.value_commitment_randomness, true);
-        self.increment_binding_verification_key(&proof.merkle_note.value_commitment, true);
-
-        self.receipts.push(proof);
-        self.transaction_fee -= note.value as i64;
+        self.add_receipt_proof(proof, note.value);
 
         Ok(())
     }
 
+    /// Add a receipt proof that was created externally.
+    ///
+    /// Like add_spend_proof, this allows receipts to be proven in parallel
+    /// before they are added to the transaction.
+    pub fn add_receipt_proof(&mut self, receipt: ReceiptParams<J>, note_value: u64) {
+        self.increment_binding_signature_key(&receipt.value_commitment_randomness, true);
+        self.increment_binding_verification_key(&receipt.merkle_note.value_commitment, true);
+
+        self.receipts.push(receipt);
+        self.transaction_fee -= note_value as i64;
+    }
+
     /// Post the transaction. This performs a bit of validation, and signs
     /// the spends with a signature that proves the spends are part of this
     /// transaction.
@@ -164,11 +184,13 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
                 self.sapling.clone(),
                 change_address,
                 change_amount as u64, // we checked it was positive
-                Memo([0; 32]),
+                Memo::default(),
             );
             self.receive(&spender_key, &change_note)?;
         }
-        self._partial_post()
+        let transaction = self._partial_post()?;
+        self.posted = true;
+        Ok(transaction)
     }
 
     /// Special case for posting a miners fee transaction. Miner fee transactions
@@ -186,7 +208,9 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
             .expect("bounds checked above")
             .merkle_note
             .note_encryption_keys = *NOTE_ENCRYPTION_MINER_KEYS;
-        self._partial_post()
+        let transaction = self._partial_post()?;
+        self.posted = true;
+        Ok(transaction)
     }
     /// Super special case for generating an illegal transaction for the genesis block.
     /// Don't bother using this anywhere else, it won't pass verification.
@@ -219,7 +243,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
             receipt_proofs.push(receipt.post()?);
         }
         Ok(Transaction {
-            sapling: self.sapling.clone(),
+            verifier: self.sapling.verifier().clone(),
             expiration_sequence: self.expiration_sequence,
             transaction_fee: self.transaction_fee,
             spends: spend_proofs,
@@ -352,8 +376,9 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> ProposedTransaction<J> {
 /// This is the serializable form of a transaction.
 #[derive(Clone)]
 pub struct Transaction<J: JubjubEngine + pairing::MultiMillerLoop> {
-    /// reference to the sapling object associated with this transaction
-    sapling: Arc<Sapling<J>>,
+    /// reference to the verifier associated with this transaction. Reading
+    /// and verifying a transaction doesn't need the proving parameters.
+    verifier: Arc<SaplingVerifier<J>>,
 
     /// The balance of total spends - outputs, which is the amount that the miner gets to keep
     transaction_fee: i64,
@@ -379,7 +404,7 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Transaction<J> {
     /// This is the main entry-point when reconstructing a serialized transaction
     /// for verifying.
     pub fn read<R: io::Read>(
-        sapling: Arc<Sapling<J>>,
+        verifier: Arc<SaplingVerifier<J>>,
         mut reader: R,
     ) -> Result<Self, TransactionError> {
         let num_spends = reader.read_u64::<LittleEndian>()?;
@@ -389,15 +414,15 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Transaction<J> {
         let mut spends = vec![];
         let mut receipts = vec![];
         for _ in 0..num_spends {
-            spends.push(SpendProof::read(&sapling.jubjub, &mut reader)?);
+            spends.push(SpendProof::read(&verifier.jubjub, &mut reader)?);
         }
         for _ in 0..num_receipts {
-            receipts.push(ReceiptProof::read(sapling.clone(), &mut reader)?);
+            receipts.push(ReceiptProof::read(&verifier.jubjub, &mut reader)?);
         }
         let binding_signature = Signature::read(&mut reader)?;
 
         Ok(Transaction {
-            sapling,
+            verifier,
             transaction_fee,
             spends,
             receipts,
@@ -432,32 +457,32 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Transaction<J> {
     ///     containing those proofs (and only those proofs)
     ///
     pub fn verify(&self) -> Result<(), TransactionError> {
-        // Context to accumulate a signature of all the spends and outputs and
-        // guarantee they are part of this transaction, unmodified.
-        let mut binding_verification_key = edwards::Point::zero();
+        self.verify_with(&self.verifier)
+    }
 
+    /// Validate the transaction like `verify`, using the verifying keys in
+    /// the given verifier instead of the ones in the verifier the
+    /// transaction was read with.
+    pub fn verify_with(&self, verifier: &SaplingVerifier<J>) -> Result<(), TransactionError> {
         for spend in self.spends.iter() {
-            spend.verify_proof(&self.sapling)?;
-            let mut tmp = spend.value_commitment.clone();
-            tmp = tmp.add(&binding_verification_key, &self.sapling.jubjub);
-            binding_verification_key = tmp;
+            spend.verify_proof(verifier)?;
         }
 
         for receipt in self.receipts.iter() {
-            receipt.verify_proof(&self.sapling)?;
-            let mut tmp = receipt.merkle_note.value_commitment.clone();
-            tmp = tmp.negate();
-            tmp = tmp.add(&binding_verification_key, &self.sapling.jubjub);
-            binding_verification_key = tmp;
+            receipt.verify_proof(verifier)?;
         }
 
+        // Context to accumulate a signature of all the spends and outputs and
+        // guarantee they are part of this transaction, unmodified.
+        let binding_verification_key = self.binding_verification_key();
+
         let hash_to_verify_signature = self.transaction_signature_hash();
 
         for spend in self.spends.iter() {
-            spend.verify_signature(&self.sapling.jubjub, &hash_to_verify_signature)?;
+            spend.verify_signature(&verifier.jubjub, &hash_to_verify_signature)?;
         }
 
-        self.verify_binding_signature(&self.sapling, &binding_verification_key)?;
+        self.verify_binding_signature(verifier, &binding_verification_key)?;
 
         Ok(())
     }
@@ -533,19 +558,41 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Transaction<J> {
         hash_result
     }
 
-    /// Confirm that this transaction was signed by the values it contains.
-    /// Called from the public verify function.
-    fn verify_binding_signature(
+    /// Sum of the value commitments of the spends minus those of the
+    /// receipts. Together with the transaction fee, this determines the key
+    /// that the binding signature was made with.
+    pub(crate) fn binding_verification_key(&self) -> edwards::Point<J, Unknown> {
+        let mut binding_verification_key = edwards::Point::zero();
+
+        for spend in self.spends.iter() {
+            let mut tmp = spend.value_commitment.clone();
+            tmp = tmp.add(&binding_verification_key, &self.verifier.jubjub);
+            binding_verification_key = tmp;
+        }
+
+        for receipt in self.receipts.iter() {
+            let mut tmp = receipt.merkle_note.value_commitment.clone();
+            tmp = tmp.negate();
+            tmp = tmp.add(&binding_verification_key, &self.verifier.jubjub);
+            binding_verification_key = tmp;
+        }
+
+        binding_verification_key
+    }
+
+    /// Construct the public key the binding signature should verify against,
+    /// and the message it signs.
+    pub(crate) fn binding_signature_data(
         &self,
-        sapling: &Sapling<J>,
+        verifier: &SaplingVerifier<J>,
         binding_verification_key: &edwards::Point<J, Unknown>,
-    ) -> Result<(), TransactionError> {
+    ) -> Result<(PublicKey<J>, [u8; 64]), TransactionError> {
         let mut value_balance_point =
-            value_balance_to_point(self.transaction_fee, &sapling.jubjub)?;
+            value_balance_to_point(self.transaction_fee, &verifier.jubjub)?;
         value_balance_point = value_balance_point.negate();
 
         let mut public_key_point = binding_verification_key.clone();
-        public_key_point = public_key_point.add(&value_balance_point, &sapling.jubjub);
+        public_key_point = public_key_point.add(&value_balance_point, &verifier.jubjub);
         let public_key = PublicKey(public_key_point);
 
         let mut data_to_verify_signature = [0; 64];
@@ -555,11 +602,24 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> Transaction<J> {
             .expect("Should be able to copy key");
         (&mut data_to_verify_signature[32..]).copy_from_slice(&self.transaction_signature_hash());
 
+        Ok((public_key, data_to_verify_signature))
+    }
+
+    /// Confirm that this transaction was signed by the values it contains.
+    /// Called from the public verify function.
+    fn verify_binding_signature(
+        &self,
+        verifier: &SaplingVerifier<J>,
+        binding_verification_key: &edwards::Point<J, Unknown>,
+    ) -> Result<(), TransactionError> {
+        let (public_key, data_to_verify_signature) =
+            self.binding_signature_data(verifier, binding_verification_key)?;
+
         if !public_key.verify(
             &data_to_verify_signature,
             &self.binding_signature,
             FixedGenerators::ValueCommitmentRandomness,
-            &sapling.jubjub,
+            &verifier.jubjub,
         ) {
             Err(TransactionError::VerificationFailed)
         } else {
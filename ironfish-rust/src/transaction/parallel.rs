@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use super::{ProposedTransaction, Transaction};
+use crate::{
+    errors::{SaplingProofError, TransactionError},
+    keys::{PublicAddress, SaplingKey},
+    note::Note,
+    receiving::ReceiptParams,
+    spending::SpendParams,
+    witness::{Witness, WitnessTrait},
+    Sapling,
+};
+use rayon::prelude::*;
+use std::sync::Arc;
+use zcash_primitives::jubjub::JubjubEngine;
+
+/// Collects the spends and receipts of a transaction without proving them,
+/// then creates all the proofs in parallel when the transaction is posted.
+///
+/// `ProposedTransaction::spend` and `receive` each create a proof before
+/// returning, so a transaction with many inputs takes as long to build as all
+/// of its proofs put together. Proofs are independent of each other, so
+/// this builder hands them to rayon instead, and then adds them to a
+/// ProposedTransaction with `add_spend_proof` and `add_receipt_proof`.
+///
+/// Only available with the `native` feature.
+pub struct ParallelTransactionBuilder<J: JubjubEngine + pairing::MultiMillerLoop> {
+    /// reference to the sapling object associated with this transaction
+    sapling: Arc<Sapling<J>>,
+
+    /// Notes to spend, with the key that owns each and its witness.
+    spends: Vec<(SaplingKey<J>, Note<J>, Witness<J>)>,
+
+    /// Notes to create, with the key of the spender creating each.
+    receipts: Vec<(SaplingKey<J>, Note<J>)>,
+
+    /// Sequence the transaction expires at; see
+    /// `ProposedTransaction::set_expiration_sequence`.
+    expiration_sequence: u32,
+}
+
+impl<J: JubjubEngine + pairing::MultiMillerLoop> ParallelTransactionBuilder<J>
+where
+    J::Params: Send + Sync,
+{
+    pub fn new(sapling: Arc<Sapling<J>>) -> Self {
+        ParallelTransactionBuilder {
+            sapling,
+            spends: vec![],
+            receipts: vec![],
+            expiration_sequence: 0,
+        }
+    }
+
+    /// Add a note owned by spender_key to be spent at the given witness
+    /// location. The proof is created when the transaction is posted.
+    pub fn spend(
+        &mut self,
+        spender_key: SaplingKey<J>,
+        note: &Note<J>,
+        witness: &dyn WitnessTrait<J>,
+    ) {
+        let witness = Witness {
+            hasher: self.sapling.clone(),
+            tree_size: witness.tree_size() as usize,
+            root_hash: witness.root_hash(),
+            auth_path: witness.get_auth_path(),
+        };
+        self.spends.push((spender_key, note.clone(), witness));
+    }
+
+    /// Add a new note owned by the recipient. The proof is created when the
+    /// transaction is posted.
+    pub fn receive(&mut self, spender_key: &SaplingKey<J>, note: &Note<J>) {
+        self.receipts.push((spender_key.clone(), note.clone()));
+    }
+
+    /// Set the sequence to expire the transaction from the mempool.
+    pub fn set_expiration_sequence(&mut self, expiration_sequence: u32) {
+        self.expiration_sequence = expiration_sequence;
+    }
+
+    /// Create all the spend and receipt proofs in parallel, and return a
+    /// ProposedTransaction containing them.
+    pub fn prove(&self) -> Result<ProposedTransaction<J>, SaplingProofError> {
+        let sapling = &self.sapling;
+        let (spends, receipts) = rayon::join(
+            || {
+                self.spends
+                    .par_iter()
+                    .map(|(spender_key, note, witness)| {
+                        SpendParams::new(sapling.clone(), spender_key.clone(), note, witness)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            },
+            || {
+                self.receipts
+                    .par_iter()
+                    .map(|(spender_key, note)| {
+                        ReceiptParams::new(sapling.clone(), spender_key, note)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        );
+
+        let mut transaction = ProposedTransaction::new(self.sapling.clone());
+        transaction.set_expiration_sequence(self.expiration_sequence);
+        for (spend, (_, note, _)) in spends?.into_iter().zip(self.spends.iter()) {
+            transaction.add_spend_proof(spend, note.value());
+        }
+        for (receipt, (_, note)) in receipts?.into_iter().zip(self.receipts.iter()) {
+            transaction.add_receipt_proof(receipt, note.value);
+        }
+        Ok(transaction)
+    }
+
+    /// Prove and post the transaction. This behaves like
+    /// `ProposedTransaction::post`, with the change computed from the
+    /// balance of the proven transaction. The change note itself is proven
+    /// after the other proofs.
+    pub fn post(
+        self,
+        spender_key: &SaplingKey<J>,
+        change_goes_to: Option<PublicAddress<J>>,
+        intended_transaction_fee: u64,
+    ) -> Result<Transaction<J>, TransactionError> {
+        let mut transaction = self.prove()?;
+        transaction.post(spender_key, change_goes_to, intended_transaction_fee)
+    }
+}
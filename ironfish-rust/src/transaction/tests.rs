@@ -3,16 +3,22 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 #[cfg(test)]
-use super::{ProposedTransaction, SimpleTransaction, Transaction};
+use super::{BatchVerifier, ProposedTransaction, SimpleTransaction, Transaction};
 use crate::{
+    errors::BatchVerificationError,
     keys::SaplingKey,
     merkle_note::NOTE_ENCRYPTION_MINER_KEYS,
     note::{Memo, Note},
+    note_commitment_tree::NoteCommitmentTree,
     sapling_bls12,
+    serializing::hex_to_bytes,
     test_util::make_fake_witness,
+    MerkleNoteHash,
 };
-use pairing::bls12_381::Bls12;
+use pairing::bls12_381::{Bls12, Fr};
+use std::sync::Arc;
 
+use zcash_primitives::jubjub::JubjubBls12;
 use zcash_primitives::redjubjub::Signature;
 
 #[test]
@@ -25,19 +31,19 @@ fn test_transaction() {
         sapling.clone(),
         spender_key.generate_public_address(),
         42,
-        Memo([0; 32]),
+        Memo::default(),
     );
     let out_note = Note::new(
         sapling.clone(),
         receiver_key.generate_public_address(),
         40,
-        Memo([0; 32]),
+        Memo::default(),
     );
     let in_note2 = Note::new(
         sapling.clone(),
         spender_key.generate_public_address(),
         18,
-        Memo([0; 32]),
+        Memo::default(),
     );
     let witness = make_fake_witness(sapling.clone(), &in_note);
     let _witness2 = make_fake_witness(sapling.clone(), &in_note2);
@@ -82,9 +88,11 @@ fn test_transaction() {
     public_transaction
         .write(&mut serialized_transaction)
         .expect("should be able to serialize transaction");
-    let read_back_transaction: Transaction<Bls12> =
-        Transaction::read(sapling.clone(), &mut serialized_transaction[..].as_ref())
-            .expect("should be able to deserialize valid transaction");
+    let read_back_transaction: Transaction<Bls12> = Transaction::read(
+        sapling.verifier().clone(),
+        &mut serialized_transaction[..].as_ref(),
+    )
+    .expect("should be able to deserialize valid transaction");
     assert_eq!(
         public_transaction.transaction_fee,
         read_back_transaction.transaction_fee
@@ -104,6 +112,65 @@ fn test_transaction() {
     assert_eq!(serialized_transaction, serialized_again);
 }
 
+#[test]
+fn test_transaction_multiple_spends() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let in_note = Note::new(
+        sapling.clone(),
+        spender_key.generate_public_address(),
+        42,
+        Memo::default(),
+    );
+    let in_note2 = Note::new(
+        sapling.clone(),
+        spender_key.generate_public_address(),
+        18,
+        Memo::default(),
+    );
+    let out_note = Note::new(
+        sapling.clone(),
+        receiver_key.generate_public_address(),
+        50,
+        Memo::default(),
+    );
+
+    let mut tree = NoteCommitmentTree::new(sapling.clone());
+    let position = tree
+        .append(&MerkleNoteHash::new(in_note.commitment_point()))
+        .unwrap();
+    let position2 = tree
+        .append(&MerkleNoteHash::new(in_note2.commitment_point()))
+        .unwrap();
+    let witness = tree.witness(position).unwrap();
+    let witness2 = tree.witness(position2).unwrap();
+
+    let mut transaction = ProposedTransaction::new(sapling.clone());
+    transaction
+        .spend(spender_key.clone(), &in_note, &witness)
+        .expect("should be able to prove spend");
+    transaction
+        .spend(spender_key.clone(), &in_note2, &witness2)
+        .expect("should be able to prove second spend");
+    transaction
+        .receive(&spender_key, &out_note)
+        .expect("should be able to prove receipt");
+
+    let public_transaction = transaction
+        .post(&spender_key, None, 1)
+        .expect("should be able to post transaction");
+    public_transaction
+        .verify()
+        .expect("Should be able to verify transaction");
+    assert_eq!(public_transaction.spends.len(), 2);
+    assert_eq!(public_transaction.receipts.len(), 2);
+    assert_eq!(
+        public_transaction.spends[0].root_hash(),
+        public_transaction.spends[1].root_hash()
+    );
+}
+
 #[test]
 fn test_simple_transaction() {
     let sapling = sapling_bls12::SAPLING.clone();
@@ -113,8 +180,18 @@ fn test_simple_transaction() {
     let receiver_address = receiver_key.generate_public_address();
 
     let mut transaction = SimpleTransaction::new(sapling.clone(), spender_key, 0);
-    let in_note = Note::new(sapling.clone(), spender_address.clone(), 42, Memo([0; 32]));
-    let out_note = Note::new(sapling.clone(), receiver_address.clone(), 41, Memo([0; 32]));
+    let in_note = Note::new(
+        sapling.clone(),
+        spender_address.clone(),
+        42,
+        Memo::default(),
+    );
+    let out_note = Note::new(
+        sapling.clone(),
+        receiver_address.clone(),
+        41,
+        Memo::default(),
+    );
     let witness = make_fake_witness(sapling.clone(), &in_note);
 
     transaction
@@ -131,7 +208,61 @@ fn test_simple_transaction() {
 
     public_transaction
         .verify()
-        .expect("should be able to verify transaction")
+        .expect("should be able to verify transaction");
+
+    let verifier = sapling_bls12::SaplingVerifier::load(JubjubBls12::new());
+    public_transaction
+        .verify_with(&verifier)
+        .expect("should be able to verify transaction without proving parameters");
+}
+
+#[test]
+fn test_read_transaction_with_verifier() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let spender_key = SaplingKey::generate_key(sapling.clone());
+    let receiver_key = SaplingKey::generate_key(sapling.clone());
+    let in_note = Note::new(
+        sapling.clone(),
+        spender_key.generate_public_address(),
+        42,
+        Memo::default(),
+    );
+    let out_note = Note::new(
+        sapling.clone(),
+        receiver_key.generate_public_address(),
+        40,
+        Memo::default(),
+    );
+    let witness = make_fake_witness(sapling.clone(), &in_note);
+
+    let mut transaction = ProposedTransaction::new(sapling.clone());
+    transaction
+        .spend(spender_key.clone(), &in_note, &witness)
+        .expect("should be able to spend note");
+    transaction
+        .receive(&spender_key, &out_note)
+        .expect("should be able to receive note");
+    let public_transaction = transaction
+        .post(&spender_key, None, 1)
+        .expect("should be able to post transaction");
+
+    let mut serialized_transaction = vec![];
+    public_transaction
+        .write(&mut serialized_transaction)
+        .expect("should be able to serialize transaction");
+
+    // A node that only validates never loads the proving parameters
+    let verifier = Arc::new(sapling_bls12::SaplingVerifier::load(JubjubBls12::new()));
+    let read_back_transaction =
+        Transaction::read(verifier, &mut serialized_transaction[..].as_ref())
+            .expect("should be able to read transaction with only a verifier");
+    read_back_transaction
+        .verify()
+        .expect("should be able to verify transaction with only a verifier");
+    assert_eq!(
+        read_back_transaction.transaction_signature_hash(),
+        public_transaction.transaction_signature_hash()
+    );
 }
 
 #[test]
@@ -143,7 +274,7 @@ fn test_miners_fee() {
         sapling.clone(),
         receiver_key.generate_public_address(),
         42,
-        Memo([0; 32]),
+        Memo::default(),
     );
     transaction
         .receive(&receiver_key, &out_note)
@@ -163,6 +294,48 @@ fn test_miners_fee() {
     );
 }
 
+#[test]
+fn test_read_legacy_transaction() {
+    // The miners fee transaction of the genesis block, serialized before
+    // notes had versions
+    let serialized = hex_to_bytes(
+        "000000000000000001000000000000000080f14b1f14f1ff00000000b3919ea8\
+         ba0f4f0bd4e980ddc26ba81dbbd3c18757fa7c9ec03aff58a659ce023d36ad65\
+         3d9c3ade073d53ed61fdbc1eb4ebc6ceb61f444d2e00bc8185c68994831cfa41\
+         5a996f8db35a56e849fd12b249931e4ecf7baef1d08d7e75f6432a8b0129e4c9\
+         ec6527d5fe6110c0f469e3167a5b31cbec4c43cb0aa0b3a9976ef1d9e910bd3f\
+         743f2686c3b0a32b21eaca08ab5ff6f8c01242bb4070157eb28c24ad4a44b0e7\
+         b1f7137fe44ae9bdd7ba489aea7527c221b1f1c4ad24e7e24c00212b41dae018\
+         978597dd25cabe5167cc75eaf8ef3bd168e3351debf30af623fe5862ff8c374c\
+         432fa5d8c593b43737310d4745b98a23427ba545beb65cb8621be2456ff350f1\
+         5728558bee168c247688b756d0be08efab3ab633d962f70d23a7271742eca93e\
+         8e419ae88505563d7cc840acd8185ea985d2c8e21fe824b97bebc0f39e8f51cc\
+         e0c539a81f0db9eef1ab1e928a56c2f0ac1e06e6b33189a0fc83ab973d0684f9\
+         c6ac3dd68a8f200ca6c7cd47450195863d15c4bd7736ab14542cc6cac949e142\
+         65616e7374616c6b206e6f746520656e6372797074696f6e206d696e6572206b\
+         6579303030303030303030303030303030303030303030303030303030303030\
+         3030303030303030303030303030305e49be5c0cc8519cb99e6a0281382f4ee7\
+         74888a7b2af168f4a571292b9e7e543b8f8c3523aee57a48fdedc896d607f18d\
+         a592bfba90daef380cd6aaa7509105",
+    )
+    .expect("should be valid hex");
+
+    let sapling = sapling_bls12::SAPLING.clone();
+    let transaction = Transaction::read(sapling.verifier().clone(), &serialized[..])
+        .expect("should be able to read legacy transaction");
+    assert_eq!(transaction.transaction_fee(), -4200000000000000);
+    // The binding signature is over the legacy serialization of the note
+    transaction
+        .verify()
+        .expect("legacy transaction should still verify");
+
+    let mut written = vec![];
+    transaction
+        .write(&mut written)
+        .expect("should be able to serialize transaction");
+    assert_eq!(written, serialized);
+}
+
 #[test]
 fn test_transaction_signature() {
     let sapling = sapling_bls12::SAPLING.clone();
@@ -172,8 +345,18 @@ fn test_transaction_signature() {
     let receiver_address = receiver_key.generate_public_address();
 
     let mut transaction = SimpleTransaction::new(sapling.clone(), spender_key, 0);
-    let in_note = Note::new(sapling.clone(), spender_address.clone(), 42, Memo([0; 32]));
-    let out_note = Note::new(sapling.clone(), receiver_address.clone(), 41, Memo([0; 32]));
+    let in_note = Note::new(
+        sapling.clone(),
+        spender_address.clone(),
+        42,
+        Memo::default(),
+    );
+    let out_note = Note::new(
+        sapling.clone(),
+        receiver_address.clone(),
+        41,
+        Memo::default(),
+    );
     let witness = make_fake_witness(sapling.clone(), &in_note);
 
     transaction
@@ -199,3 +382,205 @@ fn test_transaction_signature() {
     Signature::read(&mut serialized_signature[..].as_ref())
         .expect("Can deserialize back into a valid Signature");
 }
+
+#[test]
+fn test_batch_verify_proofs() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let spender_key = SaplingKey::generate_key(sapling.clone());
+    let receiver_key = SaplingKey::generate_key(sapling.clone());
+    let spender_address = spender_key.generate_public_address();
+    let receiver_address = receiver_key.generate_public_address();
+
+    let mut transactions = vec![];
+    for _ in 0..2 {
+        let mut transaction = SimpleTransaction::new(sapling.clone(), spender_key.clone(), 0);
+        let in_note = Note::new(
+            sapling.clone(),
+            spender_address.clone(),
+            42,
+            Memo::default(),
+        );
+        let out_note = Note::new(
+            sapling.clone(),
+            receiver_address.clone(),
+            41,
+            Memo::default(),
+        );
+        let witness = make_fake_witness(sapling.clone(), &in_note);
+        transaction
+            .spend(&in_note, &witness)
+            .expect("should be able to spend note");
+        transaction
+            .receive(&out_note)
+            .expect("should be able to receive note");
+        transactions.push(
+            transaction
+                .post()
+                .expect("should be able to post transaction"),
+        );
+    }
+
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    for transaction in transactions.iter() {
+        batch.queue(transaction);
+    }
+    assert_eq!(batch.len(), 2);
+    batch
+        .verify_proofs()
+        .expect("should be able to verify batch");
+
+    let mut bad_spend = transactions[1].clone();
+    bad_spend.spends[0].root_hash = Fr::from(1);
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    batch.queue(&transactions[0]);
+    batch.queue(&bad_spend);
+    assert!(matches!(
+        batch.verify_proofs(),
+        Err(BatchVerificationError::InvalidSpendProof {
+            transaction: 1,
+            spend: 0
+        })
+    ));
+
+    let mut bad_receipt = transactions[0].clone();
+    bad_receipt.receipts[0].merkle_note.note_commitment = Fr::from(1);
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    batch.queue(&bad_receipt);
+    batch.queue(&transactions[1]);
+    assert!(matches!(
+        batch.verify_proofs(),
+        Err(BatchVerificationError::InvalidReceiptProof {
+            transaction: 0,
+            receipt: 0
+        })
+    ));
+}
+
+#[test]
+fn test_batch_verify_signatures() {
+    let sapling = sapling_bls12::SAPLING.clone();
+    let spender_key = SaplingKey::generate_key(sapling.clone());
+    let receiver_key = SaplingKey::generate_key(sapling.clone());
+    let spender_address = spender_key.generate_public_address();
+    let receiver_address = receiver_key.generate_public_address();
+
+    let mut transactions = vec![];
+    for _ in 0..2 {
+        let mut transaction = SimpleTransaction::new(sapling.clone(), spender_key.clone(), 0);
+        let in_note = Note::new(
+            sapling.clone(),
+            spender_address.clone(),
+            42,
+            Memo::default(),
+        );
+        let out_note = Note::new(
+            sapling.clone(),
+            receiver_address.clone(),
+            41,
+            Memo::default(),
+        );
+        let witness = make_fake_witness(sapling.clone(), &in_note);
+        transaction
+            .spend(&in_note, &witness)
+            .expect("should be able to spend note");
+        transaction
+            .receive(&out_note)
+            .expect("should be able to receive note");
+        transactions.push(
+            transaction
+                .post()
+                .expect("should be able to post transaction"),
+        );
+    }
+
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    for transaction in transactions.iter() {
+        batch.queue(transaction);
+    }
+    batch.verify().expect("should be able to verify batch");
+
+    let mut bad_spend = transactions[1].clone();
+    bad_spend.spends[0].authorizing_signature = transactions[0].spends[0].authorizing_signature;
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    batch.queue(&transactions[0]);
+    batch.queue(&bad_spend);
+    assert!(matches!(
+        batch.verify_signatures(),
+        Err(BatchVerificationError::InvalidSpendSignature {
+            transaction: 1,
+            spend: 0
+        })
+    ));
+
+    let mut bad_binding = transactions[0].clone();
+    bad_binding.binding_signature = transactions[1].binding_signature;
+    let mut batch = BatchVerifier::new(sapling.verifier());
+    batch.queue(&bad_binding);
+    batch.queue(&transactions[1]);
+    assert!(matches!(
+        batch.verify(),
+        Err(BatchVerificationError::InvalidBindingSignature { transaction: 0 })
+    ));
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn test_parallel_transaction_builder() {
+    use super::ParallelTransactionBuilder;
+
+    let sapling = sapling_bls12::SAPLING.clone();
+    let spender_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let receiver_key: SaplingKey<Bls12> = SaplingKey::generate_key(sapling.clone());
+    let in_note = Note::new(
+        sapling.clone(),
+        spender_key.generate_public_address(),
+        42,
+        Memo::default(),
+    );
+    let in_note2 = Note::new(
+        sapling.clone(),
+        spender_key.generate_public_address(),
+        18,
+        Memo::default(),
+    );
+    let out_note = Note::new(
+        sapling.clone(),
+        receiver_key.generate_public_address(),
+        50,
+        Memo::default(),
+    );
+
+    let mut tree = NoteCommitmentTree::new(sapling.clone());
+    let position = tree
+        .append(&MerkleNoteHash::new(in_note.commitment_point()))
+        .unwrap();
+    let position2 = tree
+        .append(&MerkleNoteHash::new(in_note2.commitment_point()))
+        .unwrap();
+
+    let mut transaction = ParallelTransactionBuilder::new(sapling.clone());
+    transaction.spend(
+        spender_key.clone(),
+        &in_note,
+        &tree.witness(position).unwrap(),
+    );
+    transaction.spend(
+        spender_key.clone(),
+        &in_note2,
+        &tree.witness(position2).unwrap(),
+    );
+    transaction.receive(&spender_key, &out_note);
+    transaction.set_expiration_sequence(1337);
+
+    let public_transaction = transaction
+        .post(&spender_key, None, 1)
+        .expect("should be able to post transaction");
+    public_transaction
+        .verify()
+        .expect("should be able to verify transaction");
+    assert_eq!(public_transaction.spends.len(), 2);
+    // The receipt and the change
+    assert_eq!(public_transaction.receipts.len(), 2);
+    assert_eq!(public_transaction.transaction_fee(), 1);
+    assert_eq!(public_transaction.expiration_sequence(), 1337);
+}
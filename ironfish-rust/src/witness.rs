@@ -2,11 +2,20 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use super::{MerkleNoteHash, Sapling};
+use super::{
+    serializing::{bytes_to_hex, hex_to_bytes, read_scalar},
+    MerkleNoteHash, Sapling,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::PrimeField;
 use std::fmt::{self, Debug};
-use std::sync::Arc;
+use std::{convert::TryFrom, io, sync::Arc};
 
 use zcash_primitives::jubjub::JubjubEngine;
+use zcash_proofs::circuit::sapling::TREE_DEPTH;
+
+/// Version of the binary encoding of a Witness, written as its first byte.
+const WITNESS_VERSION: u8 = 1;
 
 /// Witness to a specific node in an authentication path.
 ///
@@ -18,6 +27,39 @@ pub enum WitnessNode<H: Clone + PartialEq + Debug> {
     Right(H),
 }
 
+impl<F: PrimeField> WitnessNode<F> {
+    /// Load a WitnessNode from the given stream. The node is stored as a
+    /// single byte for the side (0 for Left, 1 for Right) followed by the
+    /// hash of the sibling.
+    pub fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let side = reader.read_u8()?;
+        let hash = read_scalar(&mut reader).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unable to convert sibling hash",
+            )
+        })?;
+        match side {
+            0 => Ok(WitnessNode::Left(hash)),
+            1 => Ok(WitnessNode::Right(hash)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid witness node side",
+            )),
+        }
+    }
+
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let (side, hash) = match self {
+            WitnessNode::Left(hash) => (0, hash),
+            WitnessNode::Right(hash) => (1, hash),
+        };
+        writer.write_u8(side)?;
+        writer.write_all(hash.to_repr().as_ref())?;
+        Ok(())
+    }
+}
+
 /// Commitment that a leaf node exists in the tree, with an authentication path
 /// and the root_hash of the tree at the time the authentication_path was
 /// calculated.
@@ -52,6 +94,79 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> PartialEq for Witness<J> {
     }
 }
 
+impl<J: JubjubEngine + pairing::MultiMillerLoop> Witness<J> {
+    /// Load a Witness from the given stream.
+    ///
+    /// The encoding is a version byte, the size of the tree as a little endian
+    /// u32, the root hash, and the `TREE_DEPTH` nodes of the authentication
+    /// path starting from the leaf.
+    pub fn read<R: io::Read>(sapling: Arc<Sapling<J>>, mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u8()?;
+        if version != WITNESS_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unsupported witness version",
+            ));
+        }
+        let tree_size = reader.read_u32::<LittleEndian>()? as usize;
+        let root_hash = read_scalar(&mut reader).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Unable to convert root hash")
+        })?;
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+        for _ in 0..TREE_DEPTH {
+            auth_path.push(WitnessNode::read(&mut reader)?);
+        }
+
+        Ok(Witness {
+            hasher: sapling,
+            tree_size,
+            root_hash,
+            auth_path,
+        })
+    }
+
+    /// Stow the bytes of this Witness in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        if self.auth_path.len() != TREE_DEPTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Authentication path has the wrong depth",
+            ));
+        }
+        let tree_size = u32::try_from(self.tree_size).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Tree size doesn't fit in the witness encoding",
+            )
+        })?;
+        writer.write_u8(WITNESS_VERSION)?;
+        writer.write_u32::<LittleEndian>(tree_size)?;
+        writer.write_all(self.root_hash.to_repr().as_ref())?;
+        for node in self.auth_path.iter() {
+            node.write(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Load a Witness from a string of hexadecimal digits
+    pub fn from_hex(sapling: Arc<Sapling<J>>, value: &str) -> io::Result<Self> {
+        match hex_to_bytes(value) {
+            Err(()) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid hex witness",
+            )),
+            Ok(bytes) => Self::read(sapling, &bytes[..]),
+        }
+    }
+
+    /// Serialized witness as hexadecimal.
+    pub fn hex_witness(&self) -> io::Result<String> {
+        let mut bytes = vec![];
+        self.write(&mut bytes)?;
+        Ok(bytes_to_hex(&bytes))
+    }
+}
+
 impl<J: JubjubEngine + pairing::MultiMillerLoop> WitnessTrait<J> for Witness<J> {
     fn verify(&self, my_hash: &MerkleNoteHash<J>) -> bool {
         let mut cur_hash = my_hash.0;
@@ -97,3 +212,58 @@ impl<J: JubjubEngine + pairing::MultiMillerLoop> fmt::Debug for Witness<J> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Witness, WitnessNode};
+    use crate::{note_commitment_tree::NoteCommitmentTree, sapling_bls12, MerkleNoteHash};
+    use pairing::bls12_381::{Bls12, Fr};
+
+    #[test]
+    fn test_witness_node_serialization() {
+        let node = WitnessNode::Right(Fr::from(42));
+        let mut serialized = vec![];
+        node.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), 33);
+        assert_eq!(serialized[0], 1);
+        let read_back: WitnessNode<Fr> = WitnessNode::read(&serialized[..]).unwrap();
+        assert_eq!(read_back, node);
+
+        serialized[0] = 2;
+        assert!(WitnessNode::<Fr>::read(&serialized[..]).is_err());
+    }
+
+    #[test]
+    fn test_witness_serialization() {
+        let sapling = sapling_bls12::SAPLING.clone();
+        let mut tree = NoteCommitmentTree::new(sapling.clone());
+        for i in 0..5 {
+            tree.append(&MerkleNoteHash::new(Fr::from(i))).unwrap();
+        }
+        let mut witness = tree.witness(2).unwrap();
+
+        let mut serialized = vec![];
+        witness
+            .write(&mut serialized)
+            .expect("should be able to serialize witness");
+        assert_eq!(serialized.len(), 1 + 4 + 32 + 32 * 33);
+        let read_back: Witness<Bls12> = Witness::read(sapling.clone(), &serialized[..])
+            .expect("should be able to deserialize witness");
+        assert_eq!(read_back, witness);
+
+        let hex = witness.hex_witness().unwrap();
+        let from_hex: Witness<Bls12> = Witness::from_hex(sapling.clone(), &hex).unwrap();
+        assert_eq!(from_hex, witness);
+
+        assert!(Witness::<Bls12>::from_hex(sapling.clone(), "invalid").is_err());
+        assert!(Witness::<Bls12>::read(sapling.clone(), &serialized[..40]).is_err());
+        serialized[0] = 0;
+        assert!(Witness::<Bls12>::read(sapling, &serialized[..]).is_err());
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            witness.tree_size = u32::MAX as usize + 1;
+            assert!(witness.write(&mut vec![]).is_err());
+        }
+    }
+}